@@ -0,0 +1,80 @@
+use b64_rs::encode;
+use serde::{Deserialize, Serialize};
+use std::fmt;
+
+/// How jogger proves who you are to Jira.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum AuthMode {
+    /// Jira Cloud: your email address and an API token.
+    #[default]
+    ApiToken,
+    /// Jira Server and Data Center: a Personal Access Token sent as a bearer token.
+    PersonalAccessToken,
+    /// Jira Server and Data Center: your username and password.
+    Password,
+}
+
+impl AuthMode {
+    pub const ALL: [AuthMode; 3] = [
+        AuthMode::ApiToken,
+        AuthMode::PersonalAccessToken,
+        AuthMode::Password,
+    ];
+
+    /// Whether the email/username field is used by this mode.
+    pub fn needs_username(&self) -> bool {
+        !matches!(self, AuthMode::PersonalAccessToken)
+    }
+
+    /// The value of the `Authorization` header for these credentials.
+    pub fn header(&self, username: &str, secret: &str) -> String {
+        match self {
+            AuthMode::ApiToken | AuthMode::Password => {
+                format!("Basic {}", encode(&format!("{username}:{secret}")))
+            }
+            AuthMode::PersonalAccessToken => format!("Bearer {secret}"),
+        }
+    }
+}
+
+impl fmt::Display for AuthMode {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            AuthMode::ApiToken => write!(f, "Cloud API Token"),
+            AuthMode::PersonalAccessToken => write!(f, "Data Center Personal Access Token"),
+            AuthMode::Password => write!(f, "Username and Password"),
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::AuthMode;
+    use crate::preferences::Preferences;
+
+    #[test]
+    fn basic_auth_for_api_tokens() {
+        assert_eq!(
+            AuthMode::ApiToken.header("me@example.com", "token"),
+            "Basic bWVAZXhhbXBsZS5jb206dG9rZW4="
+        );
+    }
+
+    #[test]
+    fn bearer_auth_for_personal_access_tokens() {
+        assert_eq!(
+            AuthMode::PersonalAccessToken.header("ignored", "token"),
+            "Bearer token"
+        );
+    }
+
+    #[test]
+    fn legacy_preferences_use_api_tokens() {
+        let mut legacy = serde_json::to_value(Preferences::new()).unwrap();
+        legacy.as_object_mut().unwrap().remove("auth_mode");
+
+        let prefs: Preferences = serde_json::from_value(legacy).unwrap();
+        assert_eq!(prefs.auth_mode, AuthMode::ApiToken);
+    }
+}
@@ -0,0 +1,264 @@
+use reqwest::blocking::Client;
+use serde::{Deserialize, Serialize};
+use std::{fmt, path::PathBuf, time::Duration};
+use time::OffsetDateTime;
+
+use crate::files::write_atomic;
+use crate::meeting_types::{Meeting, Project};
+use crate::paths::cache_file;
+use crate::time::local_now;
+
+const CACHE_FILENAME: &str = "catalogs.json";
+const LEGACY_FILENAME: &str = "jogger.catalogs";
+
+/// Meetings maintained by someone else, as last fetched from `source`.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SharedCatalog {
+    /// The file path or URL the catalog came from.
+    pub source: String,
+    /// Unix timestamp of the last successful fetch.
+    pub fetched: i64,
+    pub projects: Vec<Project>,
+}
+
+impl SharedCatalog {
+    /// When the catalog was last fetched, in local time.
+    pub fn fetched(&self) -> OffsetDateTime {
+        OffsetDateTime::from_unix_timestamp(self.fetched)
+            .map(|fetched| fetched.to_offset(local_now().offset()))
+            .unwrap_or_else(|_| local_now())
+    }
+
+    /// How many meetings the catalog defines across all its projects.
+    pub fn meeting_count(&self) -> usize {
+        self.projects
+            .iter()
+            .map(|project| project.meetings.len())
+            .sum()
+    }
+}
+
+#[derive(Debug)]
+pub struct Error {
+    pub source: String,
+    pub message: String,
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "Could not load `{}`: {}", self.source, self.message)
+    }
+}
+
+impl std::error::Error for Error {}
+
+// Catalog files either list projects directly or under a `projects` key, which is the only way
+// to write them in TOML
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum CatalogFile {
+    Projects(Vec<Project>),
+    Table { projects: Vec<Project> },
+}
+
+impl From<CatalogFile> for Vec<Project> {
+    fn from(file: CatalogFile) -> Self {
+        match file {
+            CatalogFile::Projects(projects) | CatalogFile::Table { projects } => projects,
+        }
+    }
+}
+
+/// Reads the catalog at `source`, an `http(s)://` URL or a path to a JSON or TOML file.
+pub fn load_catalog(source: &str) -> Result<Vec<Project>, Error> {
+    let error = |message: String| Error {
+        source: source.to_string(),
+        message,
+    };
+
+    let input = if source.starts_with("http://") || source.starts_with("https://") {
+        Client::builder()
+            .timeout(Duration::from_secs(10))
+            .build()
+            .and_then(|client| client.get(source).send())
+            .and_then(|response| response.error_for_status())
+            .and_then(|response| response.text())
+            .map_err(|e| error(e.to_string()))?
+    } else {
+        std::fs::read_to_string(local_path(source)).map_err(|e| error(e.to_string()))?
+    };
+
+    parse_catalog(&input, source.ends_with(".toml")).map_err(error)
+}
+
+// Accepts `file://` URLs and paths starting with `~/` as well as plain paths
+fn local_path(source: &str) -> PathBuf {
+    let path = source.trim_start_matches("file://");
+    match path.strip_prefix("~/") {
+        Some(rest) => dirs::home_dir().unwrap_or_default().join(rest),
+        None => PathBuf::from(path),
+    }
+}
+
+// TOML is used when the name says so, otherwise JSON is tried first since a TOML catalog can also
+// start with `[`
+fn parse_catalog(input: &str, toml: bool) -> Result<Vec<Project>, String> {
+    let from_toml = || {
+        toml::from_str::<CatalogFile>(input)
+            .map(Vec::from)
+            .map_err(|e| e.to_string())
+    };
+
+    if toml {
+        return from_toml();
+    }
+
+    serde_json::from_str::<CatalogFile>(input)
+        .map(Vec::from)
+        .or_else(|json| from_toml().map_err(|_| json.to_string()))
+}
+
+/// The catalogs fetched by the last refresh, without going to the network.
+pub fn cached_catalogs() -> Vec<SharedCatalog> {
+    cache_path()
+        .and_then(std::fs::read_to_string)
+        .ok()
+        .and_then(|input| serde_json::from_str(&input).ok())
+        .unwrap_or_default()
+}
+
+// Where the last refresh was cached
+pub(crate) fn cache_path() -> Result<PathBuf, std::io::Error> {
+    cache_file(CACHE_FILENAME, LEGACY_FILENAME)
+}
+
+/// Fetches every source again and caches the results. Sources that fail keep their last good
+/// copy, and catalogs for sources that are no longer listed are dropped.
+pub fn refresh_catalogs(sources: &[String]) -> (Vec<SharedCatalog>, Vec<Error>) {
+    let cached = cached_catalogs();
+    let mut catalogs = Vec::new();
+    let mut errors = Vec::new();
+
+    for source in sources {
+        match load_catalog(source) {
+            Ok(projects) => catalogs.push(SharedCatalog {
+                source: source.clone(),
+                fetched: OffsetDateTime::now_utc().unix_timestamp(),
+                projects,
+            }),
+            Err(err) => {
+                catalogs.extend(cached.iter().find(|c| &c.source == source).cloned());
+                errors.push(err);
+            }
+        }
+    }
+
+    if let Err(err) = write_cache(&catalogs) {
+        errors.push(err);
+    }
+
+    (catalogs, errors)
+}
+
+// Replaces the cache with `catalogs`. It isn't merged with what other processes cached, since
+// a catalog lost that way is only fetched again by the next refresh.
+pub(crate) fn write_cache(catalogs: &[SharedCatalog]) -> Result<(), Error> {
+    cache_path()
+        .and_then(|path| write_atomic(&path, &serde_json::to_string_pretty(catalogs)?))
+        .map_err(|err| Error {
+            source: CACHE_FILENAME.to_string(),
+            message: err.to_string(),
+        })
+}
+
+/// Combines the user's own projects with shared ones. Projects with the same name are joined, and
+/// when two meetings share a label the user's own wins, then the earlier catalog's.
+pub(crate) fn merge(own: &[Project], shared: &[SharedCatalog]) -> Vec<Project> {
+    let mut projects = own.to_vec();
+
+    for catalog in shared {
+        for project in &catalog.projects {
+            let index = match projects
+                .iter()
+                .position(|p| p.name.eq_ignore_ascii_case(&project.name))
+            {
+                Some(index) => index,
+                None => {
+                    projects.push(Project::new(&project.name, Vec::new()));
+                    projects.len() - 1
+                }
+            };
+
+            let merged = &mut projects[index];
+            merged.rounding = merged.rounding.or(project.rounding);
+            for meeting in &project.meetings {
+                if merged.meeting(&meeting.label).is_none() {
+                    merged.meetings.push(Meeting {
+                        source: Some(catalog.source.clone()),
+                        ..meeting.clone()
+                    });
+                }
+            }
+        }
+    }
+
+    projects
+}
+
+#[cfg(test)]
+mod test {
+    use super::{merge, parse_catalog, SharedCatalog};
+    use crate::meeting_types::{Meeting, Project};
+
+    #[test]
+    fn parses_json_and_toml_catalogs() {
+        let json = r#"[{"name": "PIM", "meetings": [{"label": "Standup", "ticket": "PIM-1"}]}]"#;
+        let toml = r#"
+            [[projects]]
+            name = "PIM"
+
+            [[projects.meetings]]
+            label = "Standup"
+            ticket = "PIM-1"
+            billable = true
+        "#;
+
+        let from_json = parse_catalog(json, false).unwrap();
+        let from_toml = parse_catalog(toml, false).unwrap();
+        assert_eq!(
+            from_json[0].meetings,
+            vec![Meeting::new("Standup", "PIM-1")]
+        );
+        assert_eq!(from_toml[0].meetings[0].billable, Some(true));
+        assert!(parse_catalog("{", false).is_err());
+    }
+
+    #[test]
+    fn own_meetings_win_when_merging() {
+        let own = vec![Project::new("PIM", vec![Meeting::new("Standup", "PIM-99")])];
+        let shared = vec![SharedCatalog {
+            source: "team.json".to_string(),
+            fetched: 0,
+            projects: vec![
+                Project::new(
+                    "pim",
+                    vec![
+                        Meeting::new("Standup", "PIM-1"),
+                        Meeting::new("Retro", "PIM-2"),
+                    ],
+                ),
+                Project::new("PP", vec![Meeting::new("Standup", "PP-1")]),
+            ],
+        }];
+
+        let merged = merge(&own, &shared);
+        assert_eq!(merged.len(), 2);
+        assert_eq!(merged[0].meeting("standup").unwrap().ticket, "PIM-99");
+        assert_eq!(merged[0].meeting("standup").unwrap().source, None);
+        assert_eq!(
+            merged[0].meeting("retro").unwrap().source.as_deref(),
+            Some("team.json")
+        );
+        assert_eq!(merged[1].name, "PP");
+    }
+}
@@ -0,0 +1,586 @@
+use serde_json::{Map, Value};
+use std::{
+    collections::BTreeMap,
+    fmt, io,
+    path::{Path, PathBuf},
+};
+
+use crate::auth::AuthMode;
+use crate::meeting_types::Meeting;
+use crate::preferences::Preferences;
+use crate::rounding::Rounding;
+
+/// The layout of config files written by this version of jogger, see [`MIGRATIONS`].
+pub(crate) const CONFIG_VERSION: u64 = 1;
+
+// Each step upgrades settings written as version `i` to version `i + 1`. Files from before
+// versioning are version 0.
+const MIGRATIONS: [fn(&mut Settings); CONFIG_VERSION as usize] = [meetings_as_objects];
+
+/// The settings that can be overridden by `JOGGER_*` environment variables and `--set`.
+pub const OVERRIDABLE: [&str; 8] = [
+    "name",
+    "email",
+    "api_key",
+    "auth_mode",
+    "jira_url",
+    "workday_hours",
+    "rounding",
+    "catalog_sources",
+];
+
+// Settings by name, as found in one layer or after resolving them all
+pub(crate) type Settings = Map<String, Value>;
+
+/// Why the settings could not be loaded. Files are never changed when this happens.
+#[derive(Debug)]
+pub enum Error {
+    Io {
+        path: PathBuf,
+        err: io::Error,
+    },
+    /// The file isn't valid, pointing at where the problem is.
+    Syntax {
+        path: PathBuf,
+        line: usize,
+        column: usize,
+        message: String,
+        excerpt: String,
+    },
+    /// The file is neither JSON nor the old `NAME=value` format.
+    UnknownFormat {
+        path: PathBuf,
+    },
+    /// The file was written by a newer version of jogger.
+    TooNew {
+        path: PathBuf,
+        version: u64,
+    },
+    /// A value that is wrong for its setting, usually from the environment or command line.
+    Invalid(String),
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Error::Io { path, err } => write!(f, "Could not read {}: {err}", path.display()),
+            Error::Syntax {
+                path,
+                line,
+                column,
+                message,
+                excerpt,
+            } => write!(
+                f,
+                "{}:{line}:{column}: {message}\n{excerpt}",
+                path.display()
+            ),
+            Error::UnknownFormat { path } => write!(
+                f,
+                "{} is not in a format jogger understands, it should be JSON",
+                path.display()
+            ),
+            Error::TooNew { path, version } => write!(
+                f,
+                "{} was written by a newer version of jogger (config version {version}, this \
+                 version understands up to {CONFIG_VERSION}), update jogger to use it",
+                path.display()
+            ),
+            Error::Invalid(message) => write!(f, "{message}"),
+        }
+    }
+}
+
+impl std::error::Error for Error {}
+
+/// Where a setting's value came from, from lowest to highest precedence.
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub enum Layer {
+    #[default]
+    Default,
+    /// The system-wide defaults an organisation ships, see [`org_config_path`].
+    Org(PathBuf),
+    User(PathBuf),
+    Env(String),
+    /// `--set key=value` on the command line.
+    Cli,
+}
+
+impl fmt::Display for Layer {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Layer::Default => write!(f, "default"),
+            Layer::Org(path) => write!(f, "organisation defaults ({})", path.display()),
+            Layer::User(path) => write!(f, "user config ({})", path.display()),
+            Layer::Env(var) => write!(f, "environment variable {var}"),
+            Layer::Cli => write!(f, "command line"),
+        }
+    }
+}
+
+/// Which layer each setting came from, and what the layers above the user's file set it to, so
+/// saving doesn't copy them into the user's file.
+#[derive(Debug, Clone, Default)]
+pub struct Origins {
+    layers: BTreeMap<String, (Layer, Value)>,
+    user: Settings,
+    stack: Vec<(Layer, Settings)>, // Every layer as it was resolved
+}
+
+impl Origins {
+    pub fn of(&self, key: &str) -> Layer {
+        self.layers
+            .get(key)
+            .map(|(layer, _)| layer.clone())
+            .unwrap_or_default()
+    }
+
+    // Puts back what the user's file had for settings that still hold a value from another layer,
+    // so org defaults keep applying and credentials from the environment aren't written to disk
+    pub(crate) fn restore(&self, config: &mut Settings) {
+        for (key, (layer, value)) in &self.layers {
+            if matches!(layer, Layer::User(_)) || config.get(key) != Some(value) {
+                continue;
+            }
+
+            match self.user.get(key) {
+                Some(user) => config.insert(key.clone(), user.clone()),
+                None => config.remove(key),
+            };
+        }
+    }
+
+    // Resolves the layers again with `user` as the user's file at `path`, for when it has been
+    // saved
+    pub(crate) fn with_user(&self, path: &Path, user: Settings) -> (Settings, Origins) {
+        let mut layers: Vec<(Layer, Settings)> = self
+            .stack
+            .iter()
+            .filter(|(layer, _)| !matches!(layer, Layer::User(_)))
+            .cloned()
+            .collect();
+        let index = layers
+            .iter()
+            .position(|(layer, _)| !matches!(layer, Layer::Org(_)))
+            .unwrap_or(layers.len());
+        layers.insert(index, (Layer::User(path.to_path_buf()), user));
+
+        resolve(layers)
+    }
+}
+
+/// The settings in the config file at `path`, upgraded to [`CONFIG_VERSION`], and whether they
+/// had to be upgraded. A missing file has no settings.
+pub(crate) fn read_settings(path: &Path) -> Result<(Settings, bool), Error> {
+    match std::fs::read_to_string(path) {
+        Ok(input) => parse_settings(path, &input),
+        Err(err) if err.kind() == io::ErrorKind::NotFound => Ok((Settings::new(), false)),
+        Err(err) => Err(Error::Io {
+            path: path.to_path_buf(),
+            err,
+        }),
+    }
+}
+
+fn parse_settings(path: &Path, input: &str) -> Result<(Settings, bool), Error> {
+    let trimmed = input.trim_start();
+    if trimmed.is_empty() {
+        return Ok((Settings::new(), false));
+    }
+    if !trimmed.starts_with('{') {
+        return match is_legacy(input) {
+            true => Ok((legacy_settings(input), true)),
+            false => Err(Error::UnknownFormat {
+                path: path.to_path_buf(),
+            }),
+        };
+    }
+
+    let mut settings: Settings =
+        serde_json::from_str(input).map_err(|err| syntax_error(path, input, err))?;
+    let version = match settings.get("version") {
+        None => 0,
+        Some(version) => version.as_u64().ok_or_else(|| {
+            Error::Invalid(format!("{}: `version` should be a number", path.display()))
+        })?,
+    };
+    if version > CONFIG_VERSION {
+        return Err(Error::TooNew {
+            path: path.to_path_buf(),
+            version,
+        });
+    }
+
+    // Current files are checked against the text so mistakes can be pointed at, older ones can
+    // only be checked once they have been upgraded
+    if version == CONFIG_VERSION {
+        serde_json::from_str::<Preferences>(input).map_err(|err| syntax_error(path, input, err))?;
+        return Ok((settings, false));
+    }
+
+    for migrate in &MIGRATIONS[version as usize..] {
+        migrate(&mut settings);
+    }
+    settings.insert("version".to_string(), CONFIG_VERSION.into());
+    serde_json::from_value::<Preferences>(Value::Object(settings.clone()))
+        .map_err(|err| Error::Invalid(format!("{}: {err}", path.display())))?;
+
+    Ok((settings, true))
+}
+
+fn syntax_error(path: &Path, input: &str, err: serde_json::Error) -> Error {
+    let (line, column) = (err.line(), err.column());
+    let text = input
+        .lines()
+        .nth(line.saturating_sub(1))
+        .unwrap_or_default();
+
+    // serde_json puts the position at the end of the message, it is shown separately here
+    let message = err.to_string();
+    let message = message
+        .strip_suffix(&format!(" at line {line} column {column}"))
+        .unwrap_or(&message)
+        .to_string();
+
+    Error::Syntax {
+        path: path.to_path_buf(),
+        line,
+        column,
+        message,
+        excerpt: format!(
+            "  {text}\n  {}^",
+            " ".repeat(text.chars().take(column.saturating_sub(1)).count())
+        ),
+    }
+}
+
+// The first config files were `KEY=value` lines, with keys in upper case
+fn is_legacy(input: &str) -> bool {
+    input
+        .lines()
+        .map(str::trim)
+        .filter(|line| !line.is_empty())
+        .all(|line| {
+            line.split_once('=').is_some_and(|(key, _)| {
+                !key.is_empty() && key.chars().all(|c| c.is_ascii_uppercase() || c == '_')
+            })
+        })
+}
+
+fn legacy_settings(input: &str) -> Settings {
+    let mut settings = Settings::new();
+    for (key, value) in input.lines().filter_map(|line| line.split_once('=')) {
+        match key.trim() {
+            "NAME" => settings.insert("name".to_string(), value.into()),
+            "API_KEY" => settings.insert("api_key".to_string(), value.into()),
+            _ => None,
+        };
+    }
+    settings.insert("version".to_string(), CONFIG_VERSION.into());
+    settings
+}
+
+// Version 1 stores meetings as `{"label": .., "ticket": ..}` instead of `["Billable", "PIM-1"]`
+fn meetings_as_objects(settings: &mut Settings) {
+    let Some(Value::Array(projects)) = settings.get_mut("custom_meetings") else {
+        return;
+    };
+
+    let meetings = projects
+        .iter_mut()
+        .filter_map(|project| project.get_mut("meetings"))
+        .filter_map(Value::as_array_mut)
+        .flatten();
+    for meeting in meetings {
+        if let Ok(upgraded) =
+            serde_json::from_value::<Meeting>(meeting.clone()).and_then(serde_json::to_value)
+        {
+            *meeting = upgraded;
+        }
+    }
+}
+
+/// Where organisation-wide defaults are read from.
+pub fn org_config_path() -> PathBuf {
+    if cfg!(target_os = "macos") {
+        PathBuf::from("/Library/Application Support/Jogger/jogger.conf")
+    } else {
+        PathBuf::from("/etc/jogger/jogger.conf")
+    }
+}
+
+/// Stacks `layers` on top of each other, later ones replacing whole settings from earlier ones.
+pub(crate) fn resolve(layers: Vec<(Layer, Settings)>) -> (Settings, Origins) {
+    let mut config = Map::new();
+    let mut origins = Origins {
+        stack: layers.clone(),
+        ..Origins::default()
+    };
+
+    for (layer, values) in layers {
+        if matches!(layer, Layer::User(_)) {
+            origins.user = values.clone();
+        }
+        for (key, value) in values {
+            origins
+                .layers
+                .insert(key.clone(), (layer.clone(), value.clone()));
+            config.insert(key, value);
+        }
+    }
+
+    (config, origins)
+}
+
+/// Combines what another process saved since `base` was read with what this one is about to
+/// save: settings changed here since then come from `mine`, and everything else from `theirs`,
+/// the file as it is now.
+pub(crate) fn merge_settings(base: &Settings, mine: &Settings, mut theirs: Settings) -> Settings {
+    let keys: Vec<&String> = base.keys().chain(mine.keys()).collect();
+
+    for key in keys {
+        if base.get(key) == mine.get(key) {
+            continue;
+        }
+        match mine.get(key) {
+            Some(value) => theirs.insert(key.clone(), value.clone()),
+            None => theirs.remove(key),
+        };
+    }
+
+    theirs
+}
+
+/// The overrides found in `JOGGER_*` variables, e.g. `JOGGER_JIRA_URL`, one layer per variable.
+pub(crate) fn env_layers(
+    vars: impl Iterator<Item = (String, String)>,
+) -> Result<Vec<(Layer, Settings)>, String> {
+    let vars: BTreeMap<String, String> = vars.collect();
+
+    OVERRIDABLE
+        .iter()
+        .filter_map(|key| {
+            let var = format!("JOGGER_{}", key.to_uppercase());
+            let raw = vars.get(&var)?;
+            Some(
+                override_value(key, raw)
+                    .map(|value| {
+                        (
+                            Layer::Env(var.clone()),
+                            Map::from_iter([(key.to_string(), value)]),
+                        )
+                    })
+                    .map_err(|err| format!("{var}: {err}")),
+            )
+        })
+        .collect()
+}
+
+/// Parses a `key=value` override from the command line.
+pub fn parse_override(arg: &str) -> Result<(String, String), String> {
+    let (key, value) = arg
+        .split_once('=')
+        .ok_or_else(|| format!("`{arg}` should look like `key=value`"))?;
+    let key = key.trim().replace('-', "_");
+
+    if !OVERRIDABLE.contains(&key.as_str()) {
+        return Err(format!(
+            "`{key}` can't be overridden, use one of {}",
+            OVERRIDABLE.join(", ")
+        ));
+    }
+
+    Ok((key, value.to_string()))
+}
+
+pub(crate) fn cli_layer(overrides: &[(String, String)]) -> Result<Settings, String> {
+    overrides
+        .iter()
+        .map(|(key, raw)| {
+            override_value(key, raw)
+                .map(|value| (key.clone(), value))
+                .map_err(|err| format!("--set {key}: {err}"))
+        })
+        .collect()
+}
+
+// Overrides are plain text, turn them into what the setting is stored as
+fn override_value(key: &str, raw: &str) -> Result<Value, String> {
+    match key {
+        "workday_hours" => match raw.trim().parse::<f64>() {
+            Ok(hours) if hours > 0.0 && hours <= 24.0 => Ok(Value::from(hours)),
+            _ => Err(format!("`{raw}` is not a number of hours between 0 and 24")),
+        },
+        "rounding" => raw
+            .parse::<Rounding>()
+            .and_then(|rounding| serde_json::to_value(rounding).map_err(|e| e.to_string())),
+        "auth_mode" => serde_json::from_value::<AuthMode>(Value::from(raw.trim()))
+            .map(|_| Value::from(raw.trim()))
+            .map_err(|_| {
+                format!("`{raw}` is not a sign in method, use api_token, personal_access_token or password")
+            }),
+        "catalog_sources" => Ok(Value::from(
+            raw.split(',')
+                .map(str::trim)
+                .filter(|source| !source.is_empty())
+                .collect::<Vec<_>>(),
+        )),
+        _ => Ok(Value::from(raw)),
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{
+        cli_layer, env_layers, merge_settings, parse_override, parse_settings, resolve, Error,
+        Layer, CONFIG_VERSION,
+    };
+    use serde_json::{json, Map, Value};
+    use std::path::{Path, PathBuf};
+
+    #[test]
+    fn saves_merge_with_changes_from_other_processes() {
+        let base = layer(json!({"name": "Bee", "email": "old@test", "rounding": "exact"}));
+        // This process changed the rounding and dropped the email
+        let mine = layer(json!({"name": "Bee", "rounding": "up:15"}));
+        // Another one renamed the user and added a profile
+        let theirs = layer(json!({
+            "name": "Bea", "email": "old@test", "rounding": "exact", "profiles": []
+        }));
+
+        assert_eq!(
+            Value::Object(merge_settings(&base, &mine, theirs)),
+            json!({"name": "Bea", "rounding": "up:15", "profiles": []})
+        );
+    }
+
+    fn layer(value: Value) -> Map<String, Value> {
+        value.as_object().unwrap().clone()
+    }
+
+    #[test]
+    fn later_layers_win_and_are_reported() {
+        let org = Layer::Org(PathBuf::from("/etc/jogger/jogger.conf"));
+        let user = Layer::User(PathBuf::from("~/.config/jogger.conf"));
+        let env = env_layers([("JOGGER_API_KEY".to_string(), "from-ci".to_string())].into_iter())
+            .unwrap();
+        let cli = cli_layer(&[parse_override("rounding=up:15").unwrap()]).unwrap();
+
+        let mut layers = vec![
+            (
+                org.clone(),
+                layer(json!({"jira_url": "https://jira.org", "rounding": "exact"})),
+            ),
+            (
+                user.clone(),
+                layer(json!({"name": "Bee", "api_key": "mine"})),
+            ),
+        ];
+        layers.extend(env);
+        layers.push((Layer::Cli, cli));
+
+        let (config, origins) = resolve(layers);
+        assert_eq!(config["jira_url"], "https://jira.org");
+        assert_eq!(config["api_key"], "from-ci");
+        assert_eq!(config["rounding"], json!({"up": {"minutes": 15}}));
+        assert_eq!(origins.of("jira_url"), org);
+        assert_eq!(origins.of("name"), user);
+        assert_eq!(
+            origins.of("api_key"),
+            Layer::Env("JOGGER_API_KEY".to_string())
+        );
+        assert_eq!(origins.of("rounding"), Layer::Cli);
+        assert_eq!(origins.of("email"), Layer::Default);
+    }
+
+    #[test]
+    fn saving_keeps_other_layers_out_of_the_user_file() {
+        let (mut config, origins) = resolve(vec![
+            (
+                Layer::Org(PathBuf::new()),
+                layer(json!({"jira_url": "https://jira.org"})),
+            ),
+            (
+                Layer::User(PathBuf::new()),
+                layer(json!({"api_key": "mine"})),
+            ),
+            (
+                Layer::Env("JOGGER_API_KEY".to_string()),
+                layer(json!({"api_key": "from-ci"})),
+            ),
+        ]);
+        config.insert("name".to_string(), json!("Bee"));
+
+        origins.restore(&mut config);
+        assert_eq!(
+            Value::Object(config),
+            json!({"api_key": "mine", "name": "Bee"})
+        );
+    }
+
+    #[test]
+    fn rejects_bad_overrides() {
+        assert!(parse_override("jira_url").is_err());
+        assert!(parse_override("timer_state=x").is_err());
+        assert!(cli_layer(&[parse_override("workday-hours=lots").unwrap()]).is_err());
+        assert!(
+            env_layers([("JOGGER_AUTH_MODE".to_string(), "magic".to_string())].into_iter())
+                .is_err()
+        );
+    }
+
+    #[test]
+    fn points_at_syntax_errors() {
+        let input = "{\n  \"name\": \"Bee\",\n}\n";
+        let err = parse_settings(Path::new("jogger.conf"), input).unwrap_err();
+
+        assert!(matches!(
+            err,
+            Error::Syntax {
+                line: 3,
+                column: 1,
+                ..
+            }
+        ));
+        assert_eq!(err.to_string(), "jogger.conf:3:1: trailing comma\n  }\n  ^");
+    }
+
+    #[test]
+    fn points_at_wrong_values() {
+        let input = format!("{{\"version\": {CONFIG_VERSION}, \"workday_hours\": \"eight\"}}");
+        let err = parse_settings(Path::new("jogger.conf"), &input).unwrap_err();
+        assert!(matches!(err, Error::Syntax { line: 1, .. }));
+    }
+
+    #[test]
+    fn detects_formats() {
+        let path = Path::new("jogger.conf");
+        let (legacy, upgraded) = parse_settings(path, "NAME=Bee\nAPI_KEY=abc\n").unwrap();
+        assert!(upgraded);
+        assert_eq!(legacy["name"], "Bee");
+        assert_eq!(legacy["api_key"], "abc");
+
+        assert!(matches!(
+            parse_settings(path, "name: Bee"),
+            Err(Error::UnknownFormat { .. })
+        ));
+        assert!(matches!(
+            parse_settings(path, "{\"version\": 99}"),
+            Err(Error::TooNew { version: 99, .. })
+        ));
+        assert!(parse_settings(path, "  \n").unwrap().0.is_empty());
+    }
+
+    #[test]
+    fn migrates_unversioned_files() {
+        let input = r#"{"name": "Bee", "custom_meetings": [{"name": "PIM", "meetings": [["NonBillable", "PTD-1"]]}]}"#;
+        let (settings, upgraded) = parse_settings(Path::new("jogger.conf"), input).unwrap();
+
+        assert!(upgraded);
+        assert_eq!(settings["version"], CONFIG_VERSION);
+        assert_eq!(
+            settings["custom_meetings"][0]["meetings"][0],
+            json!({"label": "Non-Billable", "ticket": "PTD-1", "billable": false})
+        );
+    }
+}
@@ -0,0 +1,587 @@
+use chacha20poly1305::aead::{Aead, AeadCore, KeyInit, OsRng};
+use chacha20poly1305::{Key, XChaCha20Poly1305, XNonce};
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+use serde_json::Value;
+use std::collections::BTreeMap;
+use std::fmt;
+use std::io::ErrorKind;
+use std::path::{Path, PathBuf};
+
+use crate::config::Settings;
+use crate::files::{lock, write_private};
+use crate::paths::state_file;
+
+const KEY_FILENAME: &str = "credentials.key";
+const SECRETS_FILENAME: &str = "credentials.json";
+
+/// A password or token. It's hidden from `{:?}`, and only read through [`Secret::expose`].
+#[derive(Clone, Default, PartialEq, Eq)]
+pub struct Secret(String);
+
+impl Secret {
+    pub fn new(value: impl Into<String>) -> Self {
+        Secret(value.into())
+    }
+
+    /// The secret itself, for sending to Jira or showing in a password field.
+    pub fn expose(&self) -> &str {
+        &self.0
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.0.is_empty()
+    }
+}
+
+impl fmt::Debug for Secret {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self.is_empty() {
+            true => write!(f, "Secret(\"\")"),
+            false => write!(f, "Secret(\"********\")"),
+        }
+    }
+}
+
+// Settings are layered as JSON, so the value itself is serialized. `Preferences::save` swaps it
+// for a `Credential` before anything reaches the disk.
+impl Serialize for Secret {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        self.0.serialize(serializer)
+    }
+}
+
+// A config file refers to its key with a `Credential`, which `Preferences::load_with` replaces by
+// the key before the settings are read, so here it's only checked for being well formed
+impl<'de> Deserialize<'de> for Secret {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        #[derive(Deserialize)]
+        #[serde(untagged)]
+        enum Stored {
+            Plain(String),
+            #[allow(dead_code)] // Only its shape matters
+            Reference(Credential),
+        }
+
+        Ok(match Stored::deserialize(deserializer)? {
+            Stored::Plain(value) => Secret(value),
+            Stored::Reference(_) => Secret::default(),
+        })
+    }
+}
+
+/// Where a secret was put, which is all the config file keeps of it.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Credential {
+    pub store: StoreKind,
+    pub account: String,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum StoreKind {
+    /// The desktop keyring, over D-Bus.
+    SecretService,
+    /// A file encrypted with a key kept next to it, for systems without a keyring.
+    EncryptedFile,
+}
+
+impl fmt::Display for StoreKind {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            StoreKind::SecretService => write!(f, "the Secret Service"),
+            StoreKind::EncryptedFile => write!(f, "the encrypted credentials file"),
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct Error {
+    pub store: StoreKind,
+    pub message: String,
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "Could not use {}: {}", self.store, self.message)
+    }
+}
+
+impl std::error::Error for Error {}
+
+/// Somewhere secrets can be kept outside the config file.
+pub trait CredentialStore {
+    fn kind(&self) -> StoreKind;
+    fn get(&self, account: &str) -> Result<Option<Secret>, Error>;
+    fn set(&self, account: &str, secret: &Secret) -> Result<(), Error>;
+    fn delete(&self, account: &str) -> Result<(), Error>;
+}
+
+/// The store a credential of `kind` lives in.
+pub fn open(kind: StoreKind) -> Box<dyn CredentialStore> {
+    match kind {
+        #[cfg(target_os = "linux")]
+        StoreKind::SecretService => Box::new(secret_service::SecretService),
+        _ => Box::new(EncryptedFile::default()),
+    }
+}
+
+/// The stores to try when saving, best first.
+pub fn available() -> Vec<Box<dyn CredentialStore>> {
+    let mut stores: Vec<Box<dyn CredentialStore>> = Vec::new();
+    #[cfg(target_os = "linux")]
+    if secret_service::SecretService::is_running() {
+        stores.push(Box::new(secret_service::SecretService));
+    }
+    stores.push(Box::new(EncryptedFile::default()));
+    stores
+}
+
+/// Saves `secret` in the best store that accepts it, returning where it went.
+pub fn store(account: &str, secret: &Secret) -> Result<Credential, Error> {
+    let mut failure = None;
+    for store in available() {
+        match store.set(account, secret) {
+            Ok(()) => {
+                return Ok(Credential {
+                    store: store.kind(),
+                    account: account.to_string(),
+                })
+            }
+            Err(err) => failure = Some(err),
+        }
+    }
+
+    Err(failure.unwrap_or(Error {
+        store: StoreKind::EncryptedFile,
+        message: "no credential store is available".to_string(),
+    }))
+}
+
+/// Reads back a secret saved by [`store`].
+pub fn fetch(credential: &Credential) -> Result<Option<Secret>, Error> {
+    open(credential.store).get(&credential.account)
+}
+
+/// The account a Jira key is saved under, so keys for different sites don't overwrite each other.
+pub fn account(email: &str, jira_url: &str) -> String {
+    let host = jira_url
+        .trim()
+        .trim_start_matches("https://")
+        .trim_start_matches("http://")
+        .trim_end_matches('/');
+
+    match (email.trim(), host) {
+        ("", host) => host.to_string(),
+        (email, "") => email.to_string(),
+        (email, host) => format!("{email}@{host}"),
+    }
+}
+
+/// Replaces every key in `settings`, the main one and those of profiles, with a reference to a
+/// store it has been saved in. Keys in `known` are already stored and aren't saved again, and
+/// keys saved now are added to it in place of the ones they replace. Returns whether anything
+/// changed.
+pub(crate) fn store_keys(
+    settings: &mut Settings,
+    known: &mut Vec<(Credential, Secret)>,
+) -> Result<bool, Error> {
+    each_key_holder(settings, &mut |holder| {
+        let Some(Value::String(key)) = holder.get("api_key").cloned() else {
+            return Ok(false);
+        };
+
+        let key = Secret::new(key);
+        let account = account(
+            holder
+                .get("email")
+                .and_then(Value::as_str)
+                .unwrap_or_default(),
+            holder
+                .get("jira_url")
+                .and_then(Value::as_str)
+                .unwrap_or_default(),
+        );
+        // A key that couldn't be fetched is empty until the user enters it again, and keeps
+        // pointing at where it's stored meanwhile
+        let credential = match known
+            .iter()
+            .find(|(stored, stored_key)| stored.account == account && *stored_key == key)
+        {
+            Some((stored, _)) => stored.clone(),
+            None if key.is_empty() => {
+                holder.remove("api_key");
+                return Ok(true);
+            }
+            None => replace_key(known, &account, &key)?,
+        };
+
+        holder.insert("api_key".to_string(), serde_json::json!(credential));
+        Ok(true)
+    })
+}
+
+// Stores a new key for `account`. The one it replaces is deleted when it was kept in another
+// store, since the new one overwrites it otherwise.
+fn replace_key(
+    known: &mut Vec<(Credential, Secret)>,
+    account: &str,
+    key: &Secret,
+) -> Result<Credential, Error> {
+    let credential = store(account, key)?;
+
+    for (old, _) in known.iter().filter(|(old, _)| old.account == account) {
+        if old.store != credential.store {
+            open(old.store).delete(account).ok();
+        }
+    }
+    known.retain(|(old, _)| old.account != account);
+    known.push((credential.clone(), key.clone()));
+
+    Ok(credential)
+}
+
+/// Replaces every reference in `settings` with the key it refers to, returning what was found.
+/// Only keys missing from `known` are fetched. A key that can't be fetched is left empty, and why
+/// is returned along with the rest.
+pub(crate) fn fetch_keys(
+    settings: &mut Settings,
+    known: &[(Credential, Secret)],
+) -> Result<FetchedKeys, String> {
+    let mut found = Vec::new();
+    let mut failed = Vec::new();
+    each_key_holder::<String>(settings, &mut |holder| {
+        let Some(reference @ Value::Object(_)) = holder.get("api_key") else {
+            return Ok(false);
+        };
+        let credential: Credential =
+            serde_json::from_value(reference.clone()).map_err(|e| format!("api_key: {e}"))?;
+        let key = match known.iter().find(|(stored, _)| *stored == credential) {
+            Some((_, key)) => Some(key.clone()),
+            None => fetch(&credential).unwrap_or_else(|err| {
+                failed.push(err);
+                None
+            }),
+        };
+        let key = key.unwrap_or_default();
+
+        holder.insert("api_key".to_string(), Value::from(key.expose()));
+        found.push((credential, key));
+        Ok(true)
+    })?;
+
+    Ok((found, failed))
+}
+
+/// The keys [`fetch_keys`] found, and the errors for those it couldn't read.
+pub(crate) type FetchedKeys = (Vec<(Credential, Secret)>, Vec<Error>);
+
+// Runs `f` on the top level settings and on every profile
+fn each_key_holder<E>(
+    settings: &mut Settings,
+    f: &mut dyn FnMut(&mut Settings) -> Result<bool, E>,
+) -> Result<bool, E> {
+    let mut changed = f(settings)?;
+    if let Some(Value::Array(profiles)) = settings.get_mut("profiles") {
+        for profile in profiles.iter_mut().filter_map(Value::as_object_mut) {
+            changed |= f(profile)?;
+        }
+    }
+
+    Ok(changed)
+}
+
+#[cfg(target_os = "linux")]
+mod secret_service {
+    use super::{CredentialStore, Error, Secret, StoreKind};
+    use secret_service::blocking::SecretService as Connection;
+    use secret_service::EncryptionType;
+    use std::collections::HashMap;
+
+    pub struct SecretService;
+
+    impl SecretService {
+        /// Whether a keyring answers on the session bus.
+        pub fn is_running() -> bool {
+            Connection::connect(EncryptionType::Dh).is_ok()
+        }
+    }
+
+    fn error(err: impl ToString) -> Error {
+        Error {
+            store: StoreKind::SecretService,
+            message: err.to_string(),
+        }
+    }
+
+    fn attributes(account: &str) -> HashMap<&str, &str> {
+        HashMap::from([("application", "jogger"), ("account", account)])
+    }
+
+    impl CredentialStore for SecretService {
+        fn kind(&self) -> StoreKind {
+            StoreKind::SecretService
+        }
+
+        fn get(&self, account: &str) -> Result<Option<Secret>, Error> {
+            let service = Connection::connect(EncryptionType::Dh).map_err(error)?;
+            let found = service.search_items(attributes(account)).map_err(error)?;
+            let Some(item) = found.unlocked.first().or(found.locked.first()) else {
+                return Ok(None);
+            };
+
+            item.ensure_unlocked().map_err(error)?;
+            let secret = item.get_secret().map_err(error)?;
+            String::from_utf8(secret)
+                .map(|secret| Some(Secret::new(secret)))
+                .map_err(error)
+        }
+
+        fn set(&self, account: &str, secret: &Secret) -> Result<(), Error> {
+            let service = Connection::connect(EncryptionType::Dh).map_err(error)?;
+            let collection = service.get_default_collection().map_err(error)?;
+            collection.ensure_unlocked().map_err(error)?;
+            collection
+                .create_item(
+                    &format!("Jogger: {account}"),
+                    attributes(account),
+                    secret.expose().as_bytes(),
+                    true,
+                    "text/plain",
+                )
+                .map_err(error)?;
+
+            Ok(())
+        }
+
+        fn delete(&self, account: &str) -> Result<(), Error> {
+            let service = Connection::connect(EncryptionType::Dh).map_err(error)?;
+            let found = service.search_items(attributes(account)).map_err(error)?;
+            for item in found.unlocked.iter().chain(&found.locked) {
+                item.ensure_unlocked().map_err(error)?;
+                item.delete().map_err(error)?;
+            }
+
+            Ok(())
+        }
+    }
+}
+
+/// Secrets encrypted with a random key that is created on first use. Both files are only
+/// readable by their owner. This keeps secrets out of the config and anything it's synced to,
+/// but not from someone who can read the user's files.
+pub struct EncryptedFile {
+    key_path: PathBuf,
+    secrets_path: PathBuf,
+}
+
+impl Default for EncryptedFile {
+    fn default() -> Self {
+        let dir = state_file(KEY_FILENAME, None)
+            .ok()
+            .and_then(|path| path.parent().map(Path::to_path_buf))
+            .unwrap_or_default();
+        EncryptedFile::in_dir(&dir)
+    }
+}
+
+impl EncryptedFile {
+    pub fn in_dir(dir: &Path) -> Self {
+        EncryptedFile {
+            key_path: dir.join(KEY_FILENAME),
+            secrets_path: dir.join(SECRETS_FILENAME),
+        }
+    }
+
+    fn error(err: impl ToString) -> Error {
+        Error {
+            store: StoreKind::EncryptedFile,
+            message: err.to_string(),
+        }
+    }
+
+    fn cipher(&self) -> Result<Option<XChaCha20Poly1305>, Error> {
+        match std::fs::read_to_string(&self.key_path) {
+            Ok(key) => {
+                let key = from_hex(key.trim())
+                    .filter(|key| key.len() == 32)
+                    .ok_or_else(|| Self::error("the key file is damaged"))?;
+                Ok(Some(XChaCha20Poly1305::new(Key::from_slice(&key))))
+            }
+            Err(err) if err.kind() == ErrorKind::NotFound => Ok(None),
+            Err(err) => Err(Self::error(err)),
+        }
+    }
+
+    fn new_cipher(&self) -> Result<XChaCha20Poly1305, Error> {
+        let key = XChaCha20Poly1305::generate_key(&mut OsRng);
+        write_private(&self.key_path, &to_hex(&key)).map_err(Self::error)?;
+        Ok(XChaCha20Poly1305::new(&key))
+    }
+
+    fn secrets(&self) -> Result<BTreeMap<String, String>, Error> {
+        match std::fs::read_to_string(&self.secrets_path) {
+            Ok(input) => serde_json::from_str(&input).map_err(Self::error),
+            Err(err) if err.kind() == ErrorKind::NotFound => Ok(BTreeMap::new()),
+            Err(err) => Err(Self::error(err)),
+        }
+    }
+
+    fn save(&self, secrets: &BTreeMap<String, String>) -> Result<(), Error> {
+        let output = serde_json::to_string_pretty(secrets).map_err(Self::error)?;
+        write_private(&self.secrets_path, &output).map_err(Self::error)
+    }
+}
+
+impl CredentialStore for EncryptedFile {
+    fn kind(&self) -> StoreKind {
+        StoreKind::EncryptedFile
+    }
+
+    fn get(&self, account: &str) -> Result<Option<Secret>, Error> {
+        let Some(sealed) = self.secrets()?.remove(account) else {
+            return Ok(None);
+        };
+        let cipher = self
+            .cipher()?
+            .ok_or_else(|| Self::error("the key file is missing"))?;
+
+        // Each secret is stored as its nonce followed by the ciphertext
+        let sealed = from_hex(&sealed).ok_or_else(|| Self::error("a secret is damaged"))?;
+        if sealed.len() < 24 {
+            return Err(Self::error("a secret is damaged"));
+        }
+        let (nonce, ciphertext) = sealed.split_at(24);
+        let plain = cipher
+            .decrypt(XNonce::from_slice(nonce), ciphertext)
+            .map_err(|_| Self::error("a secret could not be decrypted with the key file"))?;
+
+        String::from_utf8(plain)
+            .map(|secret| Some(Secret::new(secret)))
+            .map_err(Self::error)
+    }
+
+    fn set(&self, account: &str, secret: &Secret) -> Result<(), Error> {
+        // Also keeps two processes from each creating a key
+        let _lock = lock(&self.secrets_path).map_err(Self::error)?;
+        let cipher = match self.cipher()? {
+            Some(cipher) => cipher,
+            None => self.new_cipher()?,
+        };
+        let nonce = XChaCha20Poly1305::generate_nonce(&mut OsRng);
+        let ciphertext = cipher
+            .encrypt(&nonce, secret.expose().as_bytes())
+            .map_err(|_| Self::error("the secret could not be encrypted"))?;
+
+        let mut secrets = self.secrets()?;
+        secrets.insert(
+            account.to_string(),
+            to_hex(&[nonce.as_slice(), &ciphertext].concat()),
+        );
+        self.save(&secrets)
+    }
+
+    fn delete(&self, account: &str) -> Result<(), Error> {
+        let _lock = lock(&self.secrets_path).map_err(Self::error)?;
+        let mut secrets = self.secrets()?;
+        if secrets.remove(account).is_some() {
+            self.save(&secrets)?;
+        }
+
+        Ok(())
+    }
+}
+
+fn to_hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|byte| format!("{byte:02x}")).collect()
+}
+
+fn from_hex(hex: &str) -> Option<Vec<u8>> {
+    if !hex.len().is_multiple_of(2) {
+        return None;
+    }
+    (0..hex.len())
+        .step_by(2)
+        .map(|i| u8::from_str_radix(hex.get(i..i + 2)?, 16).ok())
+        .collect()
+}
+
+#[cfg(test)]
+mod test {
+    use super::{
+        account, store_keys, Credential, CredentialStore, EncryptedFile, Secret, StoreKind,
+    };
+    use crate::config::Settings;
+
+    #[test]
+    fn secrets_are_hidden_from_debug_output() {
+        let secret = Secret::new("hunter2");
+        assert_eq!(format!("{secret:?}"), "Secret(\"********\")");
+        assert_eq!(secret.expose(), "hunter2");
+        assert_eq!(
+            serde_json::from_str::<Secret>(r#"{"store": "encrypted_file", "account": "me"}"#)
+                .unwrap(),
+            Secret::default()
+        );
+    }
+
+    #[test]
+    fn encrypted_file_round_trip() {
+        let dir = std::env::temp_dir().join(format!("jogger-credentials-{}", std::process::id()));
+        let store = EncryptedFile::in_dir(&dir);
+
+        assert_eq!(store.get("me@jira.test").unwrap(), None);
+        store.set("me@jira.test", &Secret::new("token")).unwrap();
+        assert_eq!(
+            store.get("me@jira.test").unwrap(),
+            Some(Secret::new("token"))
+        );
+        assert!(!std::fs::read_to_string(dir.join("credentials.json"))
+            .unwrap()
+            .contains("token"));
+
+        store.delete("me@jira.test").unwrap();
+        assert_eq!(store.get("me@jira.test").unwrap(), None);
+        std::fs::remove_dir_all(dir).ok();
+    }
+
+    #[test]
+    fn stored_keys_are_referred_to_again() {
+        let credential = |account: &str| Credential {
+            store: StoreKind::EncryptedFile,
+            account: account.to_string(),
+        };
+        let mut known = vec![
+            (credential("me@jira.test"), Secret::new("token")),
+            // One that couldn't be fetched when loading
+            (credential("jira.other"), Secret::default()),
+        ];
+
+        let mut settings: Settings = serde_json::from_value(serde_json::json!({
+            "email": "me",
+            "jira_url": "https://jira.test",
+            "api_key": "token",
+            "profiles": [{ "jira_url": "https://jira.other", "api_key": "" }],
+        }))
+        .unwrap();
+        assert!(store_keys(&mut settings, &mut known).unwrap());
+
+        assert_eq!(
+            settings["api_key"],
+            serde_json::json!(credential("me@jira.test"))
+        );
+        assert_eq!(
+            settings["profiles"][0]["api_key"],
+            serde_json::json!(credential("jira.other"))
+        );
+        assert_eq!(known.len(), 2);
+    }
+
+    #[test]
+    fn accounts_name_the_site() {
+        assert_eq!(
+            account("me@example.com", "https://jira.example.com/"),
+            "me@example.com@jira.example.com"
+        );
+        assert_eq!(account("", "https://jira.example.com"), "jira.example.com");
+    }
+}
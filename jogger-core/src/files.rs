@@ -0,0 +1,100 @@
+use std::fs::{File, OpenOptions};
+use std::io::{Error, Write};
+use std::path::{Path, PathBuf};
+
+use crate::paths::create_parent;
+
+/// An advisory lock on a file, held until dropped. Every jogger process takes it before reading a
+/// file it's about to write back, so they take turns instead of overwriting each other.
+pub(crate) struct FileLock(File);
+
+impl Drop for FileLock {
+    fn drop(&mut self) {
+        self.0.unlock().ok();
+    }
+}
+
+/// Waits for and takes the lock that goes with `path`. It's a separate file, since `path` itself
+/// is replaced rather than written to.
+pub(crate) fn lock(path: &Path) -> Result<FileLock, Error> {
+    create_parent(path)?;
+    let file = OpenOptions::new()
+        .create(true)
+        .truncate(false)
+        .write(true)
+        .open(sibling(path, ".lock"))?;
+    file.lock()?;
+
+    Ok(FileLock(file))
+}
+
+/// Replaces `path` with `contents` in one step, so nothing ever reads half a file. The file keeps
+/// its permissions.
+pub(crate) fn write_atomic(path: &Path, contents: &str) -> Result<(), Error> {
+    replace(path, contents, false)
+}
+
+/// Like [`write_atomic`], but the file is only ever readable by its owner.
+pub(crate) fn write_private(path: &Path, contents: &str) -> Result<(), Error> {
+    replace(path, contents, true)
+}
+
+fn replace(path: &Path, contents: &str, private: bool) -> Result<(), Error> {
+    create_parent(path)?;
+    let temp = sibling(path, &format!(".{}.tmp", std::process::id()));
+
+    let mut options = OpenOptions::new();
+    options.write(true).create(true).truncate(true);
+    #[cfg(unix)]
+    if private {
+        std::os::unix::fs::OpenOptionsExt::mode(&mut options, 0o600);
+    }
+
+    let written = options.open(&temp).and_then(|mut file| {
+        file.write_all(contents.as_bytes())?;
+        if let (false, Ok(existing)) = (private, std::fs::metadata(path)) {
+            file.set_permissions(existing.permissions())?;
+        }
+        file.sync_all()
+    });
+
+    match written.and_then(|_| std::fs::rename(&temp, path)) {
+        Ok(()) => Ok(()),
+        Err(err) => {
+            std::fs::remove_file(&temp).ok();
+            Err(err)
+        }
+    }
+}
+
+// `path` with `suffix` added to its file name
+fn sibling(path: &Path, suffix: &str) -> PathBuf {
+    let mut name = path.file_name().unwrap_or_default().to_owned();
+    name.push(suffix);
+    path.with_file_name(name)
+}
+
+#[cfg(test)]
+mod test {
+    use super::{lock, write_atomic};
+
+    #[test]
+    fn atomic_writes_replace_the_file() {
+        let dir = std::env::temp_dir().join(format!("jogger-files-{}", std::process::id()));
+        let path = dir.join("jogger.conf");
+
+        write_atomic(&path, "first").unwrap();
+        let held = lock(&path).unwrap();
+        write_atomic(&path, "second").unwrap();
+        drop(held);
+
+        assert_eq!(std::fs::read_to_string(&path).unwrap(), "second");
+        let mut names: Vec<_> = std::fs::read_dir(&dir)
+            .unwrap()
+            .map(|entry| entry.unwrap().file_name())
+            .collect();
+        names.sort();
+        assert_eq!(names, ["jogger.conf", "jogger.conf.lock"]);
+        std::fs::remove_dir_all(dir).ok();
+    }
+}
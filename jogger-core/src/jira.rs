@@ -1,23 +1,148 @@
-use b64_rs::encode;
-use reqwest::{blocking::Client, StatusCode};
-use serde::Serialize;
-use time::{macros::format_description, OffsetDateTime};
+use reqwest::{
+    blocking::{Client, RequestBuilder, Response},
+    StatusCode,
+};
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
+use std::{collections::BTreeMap, fmt, sync::Arc, time::Duration};
+use time::{format_description::FormatItem, macros::format_description, Date, OffsetDateTime};
 
-use crate::preferences::PrefRef;
+use crate::journal::{self, Frontend, JournalEntry};
+use crate::preferences::Preferences;
 
-pub struct Error(String);
+const JIRA_TIMESTAMP: &[FormatItem] = format_description!(
+    "[year]-[month]-[day]T[hour]:[minute]:[second].[subsecond digits:3][offset_hour sign:mandatory][offset_minute]"
+);
+
+#[derive(Debug)]
+pub enum Error {
+    /// The credentials were missing or rejected.
+    Unauthorized,
+    /// The credentials are valid but lack permission for the request.
+    Forbidden,
+    IssueNotFound {
+        key: String,
+    },
+    /// The worklog was deleted, or never existed on this issue.
+    WorklogNotFound {
+        key: String,
+        id: String,
+    },
+    RateLimited {
+        retry_after: Option<Duration>,
+    },
+    /// Jira could not be reached at all.
+    Network(String),
+    /// Jira refused the request, usually because a field was invalid.
+    Rejected {
+        status: u16,
+        body: String,
+    },
+    ServerError {
+        status: u16,
+        body: String,
+    },
+    /// Jira answered with something we could not understand.
+    InvalidResponse(String),
+    Timestamp(String),
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Error::Unauthorized => write!(f, "Jira rejected your credentials"),
+            Error::Forbidden => write!(f, "You do not have permission to do that in Jira"),
+            Error::IssueNotFound { key } => write!(f, "Issue `{key}` does not exist"),
+            Error::WorklogNotFound { key, id } => {
+                write!(f, "Worklog {id} on `{key}` does not exist")
+            }
+            Error::RateLimited {
+                retry_after: Some(retry_after),
+            } => write!(
+                f,
+                "Jira is rate limiting requests, try again in {} seconds",
+                retry_after.as_secs()
+            ),
+            Error::RateLimited { retry_after: None } => {
+                write!(f, "Jira is rate limiting requests, try again later")
+            }
+            Error::Network(err) => write!(f, "Network error: {err}"),
+            Error::Rejected { status, body } | Error::ServerError { status, body } => {
+                write!(f, "Jira returned {status}: {}", error_messages(body))
+            }
+            Error::InvalidResponse(err) => write!(f, "Unexpected response from Jira: {err}"),
+            Error::Timestamp(err) => write!(f, "Failed to handle timestamp: {err}"),
+        }
+    }
+}
+
+impl std::error::Error for Error {}
 
 impl Error {
-    pub fn msg(&self) -> &str {
-        &self.0
+    /// Whether the same request might succeed later without any changes.
+    pub fn is_transient(&self) -> bool {
+        matches!(
+            self,
+            Error::Network(_) | Error::ServerError { .. } | Error::RateLimited { .. }
+        )
     }
 }
 
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct ErrorBody {
+    #[serde(default)]
+    error_messages: Vec<String>,
+    #[serde(default)]
+    errors: BTreeMap<String, String>,
+}
+
+// Jira usually explains itself with a JSON body, fall back to the raw text when it doesn't
+fn error_messages(body: &str) -> String {
+    match serde_json::from_str::<ErrorBody>(body) {
+        Ok(error) if !error.error_messages.is_empty() || !error.errors.is_empty() => error
+            .error_messages
+            .into_iter()
+            .chain(
+                error
+                    .errors
+                    .into_iter()
+                    .map(|(field, message)| format!("{field}: {message}")),
+            )
+            .collect::<Vec<_>>()
+            .join("; "),
+        _ => body.to_string(),
+    }
+}
+
+/// Time to be logged to a ticket, submitted with [`JiraSession::submit`] or
+/// [`submit_or_queue`](crate::submit_or_queue).
+#[derive(Debug, Clone)]
 pub struct TimeLog {
     pub time_spent_seconds: usize,
     pub comment: String,
     pub ticket_number: String,
-    pub prefs: PrefRef,
+    pub started: Option<OffsetDateTime>, // `None` for now
+    pub frontend: Frontend,
+}
+
+/// A worklog as recorded in Jira.
+#[derive(Debug, Clone)]
+pub struct Worklog {
+    pub id: String,
+    pub ticket: String,
+    pub started: OffsetDateTime,
+    pub seconds: usize,
+    pub comment: String,
+}
+
+/// Changes to apply to an existing worklog. Fields left as `None` are not touched.
+#[derive(Debug, Clone, Default, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct WorklogUpdate {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub time_spent_seconds: Option<usize>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub comment: Option<String>,
 }
 
 #[derive(Serialize)]
@@ -28,49 +153,719 @@ struct WorklogPayload {
     started: String,
 }
 
-pub fn submit_timelog(log: &TimeLog) -> Result<(), Error> {
-    let prefs = log.prefs.borrow();
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct Page<T> {
+    total: usize,
+    #[serde(alias = "issues", alias = "worklogs")]
+    values: Vec<T>,
+}
+
+// A page of Jira Cloud's enhanced search, which hands out a token for the next page instead of
+// counting
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct TokenPage<T> {
+    issues: Vec<T>,
+    next_page_token: Option<String>,
+}
+
+#[derive(Deserialize)]
+struct IssueRef {
+    key: String,
+}
+
+/// An issue found by [`search_issues`].
+#[derive(Debug, Clone, PartialEq)]
+pub struct IssueSummary {
+    pub key: String,
+    pub summary: String,
+    pub status: String,
+}
+
+#[derive(Deserialize)]
+struct PickerResponse {
+    #[serde(default)]
+    sections: Vec<PickerSection>,
+}
+
+#[derive(Deserialize)]
+struct PickerSection {
+    #[serde(default)]
+    issues: Vec<IssueRef>,
+}
+
+impl PickerResponse {
+    // The picker lists an issue once per section it matches, e.g. both history and current search
+    fn keys(self) -> Vec<String> {
+        let mut keys: Vec<String> = Vec::new();
+        for issue in self.sections.into_iter().flat_map(|section| section.issues) {
+            if !keys.contains(&issue.key) {
+                keys.push(issue.key);
+            }
+        }
+        keys
+    }
+}
+
+#[derive(Deserialize)]
+struct IssueRecord {
+    key: String,
+    fields: IssueFields,
+}
+
+#[derive(Deserialize)]
+struct IssueFields {
+    #[serde(default)]
+    summary: String,
+    status: Option<Status>,
+}
+
+#[derive(Deserialize)]
+struct Status {
+    name: String,
+}
+
+impl From<IssueRecord> for IssueSummary {
+    fn from(issue: IssueRecord) -> Self {
+        IssueSummary {
+            key: issue.key,
+            summary: issue.fields.summary,
+            status: issue
+                .fields
+                .status
+                .map(|status| status.name)
+                .unwrap_or_default(),
+        }
+    }
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct User {
+    account_id: Option<String>,
+    key: Option<String>,
+    name: Option<String>,
+    display_name: Option<String>,
+}
+
+impl User {
+    // Cloud identifies users by account id, Server and Data Center by key or name
+    fn is(&self, other: &User) -> bool {
+        match (&self.account_id, &other.account_id) {
+            (Some(a), Some(b)) => a == b,
+            _ => match (&self.key, &other.key) {
+                (Some(a), Some(b)) => a == b,
+                _ => self.name.is_some() && self.name == other.name,
+            },
+        }
+    }
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct WorklogRecord {
+    id: String,
+    author: User,
+    #[serde(default)]
+    comment: Option<String>,
+    started: String,
+    time_spent_seconds: usize,
+}
+
+impl WorklogRecord {
+    fn into_worklog(self, ticket: &str) -> Result<Worklog, Error> {
+        let started = OffsetDateTime::parse(&self.started, JIRA_TIMESTAMP)
+            .map_err(|e| Error::Timestamp(format!("could not parse `{}`: {}", self.started, e)))?;
+
+        Ok(Worklog {
+            id: self.id,
+            ticket: ticket.to_string(),
+            started,
+            seconds: self.time_spent_seconds,
+            comment: self.comment.unwrap_or_default(),
+        })
+    }
+}
+
+/// A connection to Jira made from a copy of the settings, reusing one HTTP client for every
+/// request. It's cheap to clone and can be shared with or moved to other threads, so submissions
+/// don't have to hold up the interface.
+#[derive(Debug, Clone)]
+pub struct JiraSession {
+    prefs: Arc<Preferences>,
+    client: Client,
+}
+
+impl JiraSession {
+    pub fn new(prefs: Preferences) -> Self {
+        JiraSession {
+            prefs: Arc::new(prefs),
+            client: Client::new(),
+        }
+    }
+
+    /// The settings this session was made from. Changes made since need a new session.
+    pub fn preferences(&self) -> &Preferences {
+        &self.prefs
+    }
+
+    /// A session using `prefs` instead, sharing this one's HTTP client and its connections.
+    pub fn with_preferences(&self, prefs: Preferences) -> Self {
+        JiraSession {
+            prefs: Arc::new(prefs),
+            client: self.client.clone(),
+        }
+    }
 
-    let url = format!(
-        "{}/rest/api/2/issue/{}/worklog",
-        prefs.jira_url.trim_end_matches('/'),
-        log.ticket_number
-    );
+    /// Submits `log` and records the attempt in the journal.
+    pub fn submit(&self, log: &TimeLog) -> Result<Worklog, Error> {
+        let started = log.started.unwrap_or_else(OffsetDateTime::now_utc);
+        let result = self.post_worklog(
+            &log.ticket_number,
+            log.time_spent_seconds,
+            &log.comment,
+            started,
+        );
 
-    let started = OffsetDateTime::now_utc()
-        .format(format_description!(
-            "[year]-[month]-[day]T[hour]:[minute]:[second].000+0000"
+        journal::record(&JournalEntry::new(
+            log.frontend,
+            &log.ticket_number,
+            log.time_spent_seconds,
+            &log.comment,
+            started,
+            &result,
         ))
-        .map_err(|e| Error(format!("Failed to format timestamp: {}", e)))?;
+        .ok();
 
-    let payload = WorklogPayload {
-        time_spent_seconds: log.time_spent_seconds,
-        comment: log.comment.clone(),
-        started,
-    };
+        result
+    }
+
+    pub(crate) fn post_worklog(
+        &self,
+        ticket: &str,
+        time_spent_seconds: usize,
+        comment: &str,
+        started: OffsetDateTime,
+    ) -> Result<Worklog, Error> {
+        // Every submission passes through here, so it's rounded here, as are updates
+        let prefs = self.prefs.for_ticket(ticket);
+        let time_spent_seconds = prefs.round(ticket, time_spent_seconds).rounded;
+        let started = started
+            .format(JIRA_TIMESTAMP)
+            .map_err(|e| Error::Timestamp(e.to_string()))?;
+
+        let payload = WorklogPayload {
+            time_spent_seconds,
+            comment: comment.to_string(),
+            started,
+        };
+
+        let path = format!("issue/{ticket}/worklog");
+        let response = request(&self.client, &prefs, reqwest::Method::POST, &path)
+            .json(&payload)
+            .send()
+            .map_err(|e| Error::Network(e.to_string()))?;
+
+        match response.status() {
+            StatusCode::OK | StatusCode::CREATED => {
+                parse_json::<WorklogRecord>(response)?.into_worklog(ticket)
+            }
+            _ => Err(error_from(response, &path)),
+        }
+    }
+
+    /// Fetches a single worklog by id.
+    pub fn fetch_worklog(&self, ticket: &str, id: &str) -> Result<Worklog, Error> {
+        let record: WorklogRecord = get(
+            &self.client,
+            &self.prefs.for_ticket(ticket),
+            &format!("issue/{ticket}/worklog/{id}"),
+            &[],
+        )?;
+        record.into_worklog(ticket)
+    }
+
+    /// Applies `update` to an existing worklog, returning the worklog as Jira now has it. A new
+    /// time is rounded like a new worklog's.
+    pub fn update_worklog(
+        &self,
+        ticket: &str,
+        id: &str,
+        update: &WorklogUpdate,
+    ) -> Result<Worklog, Error> {
+        let path = format!("issue/{ticket}/worklog/{id}");
+        let prefs = self.prefs.for_ticket(ticket);
+        let update = WorklogUpdate {
+            time_spent_seconds: update
+                .time_spent_seconds
+                .map(|seconds| prefs.round(ticket, seconds).rounded),
+            ..update.clone()
+        };
+        let response = request(&self.client, &prefs, reqwest::Method::PUT, &path)
+            .json(&update)
+            .send()
+            .map_err(|e| Error::Network(e.to_string()))?;
+
+        match response.status() {
+            StatusCode::OK => parse_json::<WorklogRecord>(response)?.into_worklog(ticket),
+            _ => Err(error_from(response, &path)),
+        }
+    }
+
+    pub fn delete_worklog(&self, ticket: &str, id: &str) -> Result<(), Error> {
+        let path = format!("issue/{ticket}/worklog/{id}");
+        let prefs = self.prefs.for_ticket(ticket);
+        let response = request(&self.client, &prefs, reqwest::Method::DELETE, &path)
+            .send()
+            .map_err(|e| Error::Network(e.to_string()))?;
+
+        match response.status() {
+            StatusCode::OK | StatusCode::NO_CONTENT => Ok(()),
+            _ => Err(error_from(response, &path)),
+        }
+    }
+
+    /// Fetches the current user's worklogs started between `from` and `to`, inclusive, from
+    /// every site they log to.
+    pub fn fetch_worklogs(&self, from: Date, to: Date) -> Result<Vec<Worklog>, Error> {
+        let mut worklogs = Vec::new();
+        for site in &self.prefs.sites() {
+            worklogs.extend(self.fetch_site_worklogs(site, from, to)?);
+        }
 
-    let credentials = format!(
-        "Basic {}",
-        encode(&format!("{}:{}", prefs.email, prefs.api_key))
-    );
+        worklogs.sort_by_key(|worklog| worklog.started);
+        Ok(worklogs)
+    }
+
+    fn fetch_site_worklogs(
+        &self,
+        prefs: &Preferences,
+        from: Date,
+        to: Date,
+    ) -> Result<Vec<Worklog>, Error> {
+        let client = &self.client;
+        let myself: User = get(client, prefs, "myself", &[])?;
+
+        let jql = format!(
+            "worklogAuthor = currentUser() AND worklogDate >= \"{from}\" AND worklogDate <= \"{to}\""
+        );
+        let issues: Vec<IssueRef> = search(client, prefs, &jql, "key")?;
+
+        let mut worklogs = Vec::new();
+        for issue in issues {
+            let records: Vec<WorklogRecord> =
+                get_all(client, prefs, &format!("issue/{}/worklog", issue.key), &[])?;
+
+            for record in records.into_iter().filter(|r| r.author.is(&myself)) {
+                let worklog = record.into_worklog(&issue.key)?;
+                if (from..=to).contains(&worklog.started.date()) {
+                    worklogs.push(worklog);
+                }
+            }
+        }
+
+        Ok(worklogs)
+    }
+
+    /// Checks that Jira accepts the main credentials, returning the name of the user they
+    /// belong to.
+    pub fn test_connection(&self) -> Result<String, Error> {
+        let myself: User = get(&self.client, &self.prefs, "myself", &[])?;
+
+        Ok(myself
+            .display_name
+            .or(myself.name)
+            .or(myself.account_id)
+            .unwrap_or_default())
+    }
+
+    /// Finds issues whose key or summary matches `query`, best matches first, on every site.
+    /// Sites that can't be searched are skipped unless none can.
+    pub fn search_issues(&self, query: &str) -> Result<Vec<IssueSummary>, Error> {
+        let mut issues = Vec::new();
+        let mut failure = None;
+        for site in &self.prefs.sites() {
+            match self.search_site(site, query) {
+                Ok(found) => issues.extend(found),
+                Err(err) => failure = failure.or(Some(err)),
+            }
+        }
+
+        match failure {
+            Some(err) if issues.is_empty() => Err(err),
+            _ => Ok(issues),
+        }
+    }
+
+    fn search_site(&self, prefs: &Preferences, query: &str) -> Result<Vec<IssueSummary>, Error> {
+        let picked: PickerResponse = get(
+            &self.client,
+            prefs,
+            "issue/picker",
+            &[
+                ("query", query.trim().to_string()),
+                ("currentJQL", String::new()),
+            ],
+        )?;
+
+        let keys = picked.keys();
+        if keys.is_empty() {
+            return Ok(Vec::new());
+        }
+
+        // The picker only gives us a summary, look the statuses up in one search
+        let mut issues: Vec<IssueRecord> = search(
+            &self.client,
+            prefs,
+            &format!("key in ({})", keys.join(",")),
+            "summary,status",
+        )?;
+        Ok(keys
+            .into_iter()
+            .filter_map(|key| {
+                let index = issues.iter().position(|issue| issue.key == key)?;
+                Some(issues.swap_remove(index).into())
+            })
+            .collect())
+    }
+
+    /// Looks up a single issue, failing with [`Error::IssueNotFound`] when `key` doesn't exist.
+    pub fn fetch_issue(&self, key: &str) -> Result<IssueSummary, Error> {
+        let issue: IssueRecord = get(
+            &self.client,
+            &self.prefs.for_ticket(key),
+            &format!("issue/{}", key.trim()),
+            &[("fields", "summary,status".to_string())],
+        )?;
+
+        Ok(issue.into())
+    }
+}
+
+fn request(
+    client: &Client,
+    prefs: &Preferences,
+    method: reqwest::Method,
+    path: &str,
+) -> RequestBuilder {
+    // Paths are relative to version 2 of the API unless they start at the root of the site
+    let base = prefs.jira_url.trim_end_matches('/');
+    let url = match path.strip_prefix('/') {
+        Some(path) => format!("{base}/{path}"),
+        None => format!("{base}/rest/api/2/{path}"),
+    };
 
-    let client = Client::new();
-    let response = client
-        .post(&url)
-        .header("Authorization", credentials)
+    client
+        .request(method, url)
+        .header(
+            "Authorization",
+            prefs.auth_mode.header(&prefs.email, prefs.api_key.expose()),
+        )
         .header("Content-Type", "application/json")
         .header("Accept", "application/json")
-        .json(&payload)
+}
+
+fn get<T: DeserializeOwned>(
+    client: &Client,
+    prefs: &Preferences,
+    path: &str,
+    query: &[(&str, String)],
+) -> Result<T, Error> {
+    let response = request(client, prefs, reqwest::Method::GET, path)
+        .query(query)
         .send()
-        .map_err(|e| Error(format!("Network error: {}", e)))?;
+        .map_err(|e| Error::Network(e.to_string()))?;
 
     match response.status() {
-        StatusCode::OK | StatusCode::CREATED => Ok(()),
-        status => {
-            let error_body = response
+        StatusCode::OK => parse_json(response),
+        _ => Err(error_from(response, path)),
+    }
+}
+
+fn parse_json<T: DeserializeOwned>(response: Response) -> Result<T, Error> {
+    response
+        .json()
+        .map_err(|e| Error::InvalidResponse(e.to_string()))
+}
+
+// Follows Jira's `startAt`/`total` pagination until every page has been read
+fn get_all<T: DeserializeOwned>(
+    client: &Client,
+    prefs: &Preferences,
+    path: &str,
+    query: &[(&str, String)],
+) -> Result<Vec<T>, Error> {
+    collect_pages(|start_at| {
+        let mut page_query = query.to_vec();
+        page_query.push(("startAt", start_at.to_string()));
+        page_query.push(("maxResults", "100".to_string()));
+
+        get(client, prefs, path, &page_query)
+    })
+}
+
+fn collect_pages<T>(
+    mut fetch: impl FnMut(usize) -> Result<Page<T>, Error>,
+) -> Result<Vec<T>, Error> {
+    let mut values = Vec::new();
+
+    loop {
+        let page = fetch(values.len())?;
+        let finished = page.values.is_empty() || values.len() + page.values.len() >= page.total;
+        values.extend(page.values);
+
+        if finished {
+            return Ok(values);
+        }
+    }
+}
+
+// Every issue matching `jql`. Cloud has retired the counted search in favour of one paged by
+// token, Server and Data Center only have the counted one.
+fn search<T: DeserializeOwned>(
+    client: &Client,
+    prefs: &Preferences,
+    jql: &str,
+    fields: &str,
+) -> Result<Vec<T>, Error> {
+    let query = [("jql", jql.to_string()), ("fields", fields.to_string())];
+    if !is_cloud(&prefs.jira_url) {
+        return get_all(client, prefs, "search", &query);
+    }
+
+    collect_token_pages(|token| {
+        let mut page_query = query.to_vec();
+        page_query.push(("maxResults", "100".to_string()));
+        if let Some(token) = token {
+            page_query.push(("nextPageToken", token.to_string()));
+        }
+
+        get(client, prefs, "/rest/api/3/search/jql", &page_query)
+    })
+}
+
+fn collect_token_pages<T>(
+    mut fetch: impl FnMut(Option<&str>) -> Result<TokenPage<T>, Error>,
+) -> Result<Vec<T>, Error> {
+    let mut values = Vec::new();
+    let mut token = None;
+
+    loop {
+        let page = fetch(token.as_deref())?;
+        values.extend(page.issues);
+
+        match page.next_page_token {
+            Some(next) if Some(&next) != token.as_ref() => token = Some(next),
+            _ => return Ok(values),
+        }
+    }
+}
+
+// What a 404 for `path` means: a missing worklog for `issue/{key}/worklog/{id}`, a missing issue
+// for anything else about an issue
+fn not_found(path: &str) -> Option<Error> {
+    let mut parts = path.strip_prefix("issue/")?.split('/');
+    let key = parts.next().unwrap_or_default().to_string();
+
+    match (parts.next(), parts.next()) {
+        (Some("worklog"), Some(id)) => Some(Error::WorklogNotFound {
+            key,
+            id: id.to_string(),
+        }),
+        _ => Some(Error::IssueNotFound { key }),
+    }
+}
+
+// Atlassian hosts every Cloud site under one of its own domains
+fn is_cloud(jira_url: &str) -> bool {
+    let host = jira_url
+        .trim()
+        .trim_start_matches("https://")
+        .trim_start_matches("http://")
+        .split(['/', ':'])
+        .next()
+        .unwrap_or_default()
+        .to_ascii_lowercase();
+
+    [".atlassian.net", ".jira.com", ".jira-dev.com"]
+        .iter()
+        .any(|domain| host.ends_with(domain))
+}
+
+// `path` is used to name the issue or worklog when Jira can't find it
+fn error_from(response: Response, path: &str) -> Error {
+    let status = response.status();
+    if let (StatusCode::NOT_FOUND, Some(err)) = (status, not_found(path)) {
+        return err;
+    }
+
+    match status {
+        StatusCode::UNAUTHORIZED => Error::Unauthorized,
+        StatusCode::FORBIDDEN => Error::Forbidden,
+        StatusCode::TOO_MANY_REQUESTS => Error::RateLimited {
+            retry_after: response
+                .headers()
+                .get("Retry-After")
+                .and_then(|value| value.to_str().ok())
+                .and_then(|value| value.trim().parse().ok())
+                .map(Duration::from_secs),
+        },
+        _ => {
+            let body = response
                 .text()
                 .unwrap_or_else(|_| "Unknown error".to_string());
-            Err(Error(format!("Jira returned {}: {}", status, error_body)))
+
+            if status.is_server_error() {
+                Error::ServerError {
+                    status: status.as_u16(),
+                    body,
+                }
+            } else {
+                Error::Rejected {
+                    status: status.as_u16(),
+                    body,
+                }
+            }
         }
     }
 }
+
+#[cfg(test)]
+mod test {
+    use super::{
+        collect_pages, collect_token_pages, error_messages, is_cloud, not_found, Error,
+        JiraSession, Page, PickerResponse, TimeLog, TokenPage, WorklogRecord,
+    };
+    use time::macros::datetime;
+
+    #[test]
+    fn error_messages_from_jira_body() {
+        let body = r#"{"errorMessages":["Worklog must not be null"],"errors":{"timeLogged":"You must indicate the time spent working."}}"#;
+        assert_eq!(
+            error_messages(body),
+            "Worklog must not be null; timeLogged: You must indicate the time spent working."
+        );
+    }
+
+    #[test]
+    fn error_messages_from_plain_body() {
+        assert_eq!(error_messages("Bad Gateway"), "Bad Gateway");
+    }
+
+    #[test]
+    fn picker_keys_are_deduplicated() {
+        let body = r#"{"sections":[
+            {"label":"History Search","issues":[{"key":"PIM-6216","summaryText":"Import"}]},
+            {"label":"Current Search","issues":[{"key":"PIM-6126"},{"key":"PIM-6216"}]}
+        ]}"#;
+        let picked: PickerResponse = serde_json::from_str(body).unwrap();
+        assert_eq!(picked.keys(), vec!["PIM-6216", "PIM-6126"]);
+    }
+
+    #[test]
+    fn sessions_and_logs_can_cross_threads() {
+        fn shareable<T: Send + Sync + 'static>() {}
+        shareable::<JiraSession>();
+        shareable::<TimeLog>();
+    }
+
+    #[test]
+    fn worklogs_are_parsed_from_jira() {
+        let body = r#"{
+            "id": "10042",
+            "author": {"accountId": "5b10a2844c20165700ede21g", "displayName": "Mia"},
+            "comment": "Reviewed the import",
+            "started": "2024-03-05T09:30:00.000+0100",
+            "timeSpent": "1h 30m",
+            "timeSpentSeconds": 5400
+        }"#;
+        let record: WorklogRecord = serde_json::from_str(body).unwrap();
+        let worklog = record.into_worklog("PIM-6216").unwrap();
+
+        assert_eq!(worklog.id, "10042");
+        assert_eq!(worklog.ticket, "PIM-6216");
+        assert_eq!(worklog.seconds, 5400);
+        assert_eq!(worklog.comment, "Reviewed the import");
+        assert_eq!(worklog.started, datetime!(2024-03-05 08:30 UTC));
+    }
+
+    #[test]
+    fn worklogs_without_comments_or_valid_timestamps() {
+        let body = r#"{"id":"1","author":{"key":"mia"},"started":"2024-03-05 09:30","timeSpentSeconds":60}"#;
+        let record: WorklogRecord = serde_json::from_str(body).unwrap();
+        assert!(matches!(
+            record.into_worklog("PIM-1"),
+            Err(Error::Timestamp(_))
+        ));
+
+        let body = r#"{"id":"1","author":{"key":"mia"},"started":"2024-03-05T09:30:00.000-0500","timeSpentSeconds":60}"#;
+        let record: WorklogRecord = serde_json::from_str(body).unwrap();
+        let worklog = record.into_worklog("PIM-1").unwrap();
+        assert_eq!(worklog.comment, "");
+        assert_eq!(worklog.started, datetime!(2024-03-05 14:30 UTC));
+    }
+
+    #[test]
+    fn counted_pages_are_followed_to_the_total() {
+        let pages = [
+            r#"{"startAt":0,"maxResults":2,"total":3,"worklogs":[1,2]}"#,
+            r#"{"startAt":2,"maxResults":2,"total":3,"worklogs":[3]}"#,
+        ];
+        let mut starts = Vec::new();
+        let values = collect_pages(|start_at| {
+            starts.push(start_at);
+            Ok(serde_json::from_str::<Page<u32>>(pages[starts.len() - 1]).unwrap())
+        })
+        .unwrap();
+
+        assert_eq!(values, [1, 2, 3]);
+        assert_eq!(starts, [0, 2]);
+    }
+
+    #[test]
+    fn token_pages_are_followed_until_the_last() {
+        let pages = [
+            r#"{"issues":[{"key":"A-1"}],"nextPageToken":"second"}"#,
+            r#"{"issues":[{"key":"A-2"}],"isLast":true}"#,
+        ];
+        let mut tokens = Vec::new();
+        let values = collect_token_pages(|token| {
+            tokens.push(token.map(str::to_string));
+            Ok(
+                serde_json::from_str::<TokenPage<super::IssueRef>>(pages[tokens.len() - 1])
+                    .unwrap(),
+            )
+        })
+        .unwrap();
+
+        let keys: Vec<_> = values.into_iter().map(|issue| issue.key).collect();
+        assert_eq!(keys, ["A-1", "A-2"]);
+        assert_eq!(tokens, [None, Some("second".to_string())]);
+    }
+
+    #[test]
+    fn cloud_sites_are_recognised_by_host() {
+        assert!(is_cloud("https://acme.atlassian.net"));
+        assert!(is_cloud("https://ACME.atlassian.net/jira/"));
+        assert!(!is_cloud("https://jira.acme.com"));
+        assert!(!is_cloud("https://atlassian.net.acme.com:8080"));
+    }
+
+    #[test]
+    fn missing_worklogs_are_not_missing_issues() {
+        assert!(matches!(
+            not_found("issue/PIM-1/worklog/10042"),
+            Some(Error::WorklogNotFound { key, id }) if key == "PIM-1" && id == "10042"
+        ));
+        assert!(matches!(
+            not_found("issue/PIM-1/worklog"),
+            Some(Error::IssueNotFound { key }) if key == "PIM-1"
+        ));
+        assert!(matches!(
+            not_found("issue/PIM-1"),
+            Some(Error::IssueNotFound { .. })
+        ));
+        assert!(not_found("myself").is_none());
+    }
+}
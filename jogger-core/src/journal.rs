@@ -0,0 +1,241 @@
+use serde::{Deserialize, Serialize};
+use std::fmt;
+use std::fs::OpenOptions;
+use std::io::{Error, ErrorKind, Write};
+use time::{Date, OffsetDateTime};
+
+use crate::jira::{Error as JiraError, Worklog};
+use crate::paths::{create_parent, state_file};
+use crate::time::local_now;
+
+const JOURNAL_FILENAME: &str = "journal";
+const LEGACY_FILENAME: &str = "jogger.journal";
+
+/// The front end a worklog was submitted from.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Frontend {
+    Tui,
+    Cli,
+    MacOs,
+    /// Worklogs queued before jogger kept track of where they came from.
+    #[default]
+    Unknown,
+}
+
+impl fmt::Display for Frontend {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Frontend::Tui => write!(f, "TUI"),
+            Frontend::Cli => write!(f, "command line"),
+            Frontend::MacOs => write!(f, "macOS"),
+            Frontend::Unknown => write!(f, "unknown"),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Outcome {
+    Submitted,
+    /// Jira could not be reached and the worklog was queued.
+    Queued,
+    Failed,
+    /// A submitted worklog was changed afterwards, the entry holds what it became.
+    Updated,
+    /// A submitted worklog was deleted afterwards.
+    Deleted,
+}
+
+/// One attempt at submitting a worklog.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct JournalEntry {
+    pub recorded: i64, // Unix timestamp
+    pub ticket: String,
+    pub seconds: usize,
+    pub comment: String,
+    pub started: i64, // Unix timestamp
+    pub outcome: Outcome,
+    pub error: Option<String>,
+    pub worklog_id: Option<String>,
+    pub frontend: Frontend,
+}
+
+impl JournalEntry {
+    pub(crate) fn new(
+        frontend: Frontend,
+        ticket: &str,
+        seconds: usize,
+        comment: &str,
+        started: OffsetDateTime,
+        result: &Result<Worklog, JiraError>,
+    ) -> Self {
+        JournalEntry {
+            recorded: OffsetDateTime::now_utc().unix_timestamp(),
+            ticket: ticket.to_string(),
+            // What Jira recorded, which may have been rounded
+            seconds: result.as_ref().map_or(seconds, |worklog| worklog.seconds),
+            comment: comment.to_string(),
+            started: started.unix_timestamp(),
+            outcome: match result {
+                Ok(_) => Outcome::Submitted,
+                Err(_) => Outcome::Failed,
+            },
+            error: result.as_ref().err().map(|err| err.to_string()),
+            worklog_id: result.as_ref().ok().map(|worklog| worklog.id.clone()),
+            frontend,
+        }
+    }
+
+    /// Records a change made to `worklog` after it was submitted. `outcome` is either
+    /// [`Outcome::Updated`], with `worklog` as it is now, or [`Outcome::Deleted`].
+    pub fn edited(frontend: Frontend, worklog: &Worklog, outcome: Outcome) -> Self {
+        JournalEntry {
+            recorded: OffsetDateTime::now_utc().unix_timestamp(),
+            ticket: worklog.ticket.clone(),
+            seconds: worklog.seconds,
+            comment: worklog.comment.clone(),
+            started: worklog.started.unix_timestamp(),
+            outcome,
+            error: None,
+            worklog_id: Some(worklog.id.clone()),
+            frontend,
+        }
+    }
+
+    /// When the work started, in local time.
+    pub fn started(&self) -> OffsetDateTime {
+        OffsetDateTime::from_unix_timestamp(self.started)
+            .map(|started| started.to_offset(local_now().offset()))
+            .unwrap_or_else(|_| local_now())
+    }
+}
+
+/// Which journal entries to return. Unset fields match everything.
+#[derive(Debug, Clone, Default)]
+pub struct JournalQuery {
+    pub from: Option<Date>,
+    pub to: Option<Date>,
+    pub ticket: Option<String>,
+    pub outcome: Option<Outcome>,
+}
+
+impl JournalQuery {
+    /// Entries that started on `date`.
+    pub fn on(date: Date) -> Self {
+        JournalQuery {
+            from: Some(date),
+            to: Some(date),
+            ..Default::default()
+        }
+    }
+
+    pub fn matches(&self, entry: &JournalEntry) -> bool {
+        let started = entry.started().date();
+
+        self.from.is_none_or(|from| started >= from)
+            && self.to.is_none_or(|to| started <= to)
+            && self
+                .ticket
+                .as_ref()
+                .is_none_or(|ticket| ticket.eq_ignore_ascii_case(&entry.ticket))
+            && self.outcome.is_none_or(|outcome| outcome == entry.outcome)
+    }
+}
+
+/// Appends `entry` to the journal.
+pub fn record(entry: &JournalEntry) -> Result<(), Error> {
+    let path = state_file(JOURNAL_FILENAME, Some(LEGACY_FILENAME))?;
+    create_parent(&path)?;
+
+    let mut file = OpenOptions::new().create(true).append(true).open(path)?;
+    writeln!(file, "{}", serde_json::to_string(entry)?)
+}
+
+/// Every journal entry matching `query`, oldest first.
+pub fn query_journal(query: &JournalQuery) -> Result<Vec<JournalEntry>, Error> {
+    match std::fs::read_to_string(state_file(JOURNAL_FILENAME, Some(LEGACY_FILENAME))?) {
+        Ok(input) => Ok(parse(&input)
+            .into_iter()
+            .filter(|entry| query.matches(entry))
+            .collect()),
+        Err(err) if err.kind() == ErrorKind::NotFound => Ok(Vec::new()),
+        Err(err) => Err(err),
+    }
+}
+
+/// The tickets most recently logged to successfully, newest first and without repeats.
+pub fn recent_tickets(limit: usize) -> Result<Vec<String>, Error> {
+    let submitted = query_journal(&JournalQuery {
+        outcome: Some(Outcome::Submitted),
+        ..Default::default()
+    })?;
+
+    let mut tickets: Vec<String> = Vec::new();
+    for entry in submitted.into_iter().rev() {
+        if tickets.len() == limit {
+            break;
+        }
+        if !tickets.contains(&entry.ticket) {
+            tickets.push(entry.ticket);
+        }
+    }
+    Ok(tickets)
+}
+
+// A crash mid-write can leave a partial last line, skip anything that doesn't parse
+fn parse(input: &str) -> Vec<JournalEntry> {
+    input
+        .lines()
+        .filter_map(|line| serde_json::from_str(line).ok())
+        .collect()
+}
+
+#[cfg(test)]
+mod test {
+    use super::{parse, Frontend, JournalEntry, JournalQuery, Outcome};
+    use time::macros::{date, datetime};
+
+    fn entry(ticket: &str, started: i64, outcome: Outcome) -> JournalEntry {
+        JournalEntry {
+            recorded: started,
+            ticket: ticket.to_string(),
+            seconds: 3600,
+            comment: String::new(),
+            started,
+            outcome,
+            error: None,
+            worklog_id: None,
+            frontend: Frontend::Cli,
+        }
+    }
+
+    #[test]
+    fn skips_partial_lines() {
+        let input = format!(
+            "{}\n{{\"recorded\":17",
+            serde_json::to_string(&entry("PIM-1", 0, Outcome::Submitted)).unwrap()
+        );
+        assert_eq!(parse(&input), vec![entry("PIM-1", 0, Outcome::Submitted)]);
+    }
+
+    #[test]
+    fn query_by_day_ticket_and_outcome() {
+        // Midday, so the day is the same in any sensible timezone
+        let tuesday = datetime!(2024-01-16 12:00 UTC).unix_timestamp();
+        let wednesday = datetime!(2024-01-17 12:00 UTC).unix_timestamp();
+
+        let query = JournalQuery::on(date!(2024 - 01 - 16));
+        assert!(query.matches(&entry("PIM-1", tuesday, Outcome::Submitted)));
+        assert!(!query.matches(&entry("PIM-1", wednesday, Outcome::Submitted)));
+
+        let query = JournalQuery {
+            ticket: Some("pim-1".to_string()),
+            outcome: Some(Outcome::Failed),
+            ..Default::default()
+        };
+        assert!(query.matches(&entry("PIM-1", tuesday, Outcome::Failed)));
+        assert!(!query.matches(&entry("PIM-1", tuesday, Outcome::Submitted)));
+        assert!(!query.matches(&entry("PIM-2", tuesday, Outcome::Failed)));
+    }
+}
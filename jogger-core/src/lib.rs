@@ -1,9 +1,40 @@
+pub mod auth;
+pub mod catalogs;
+pub mod config;
+pub mod credentials;
+mod files;
 pub mod jira;
+pub mod journal;
 pub mod meeting_types;
+pub mod paths;
 pub mod preferences;
+pub mod profiles;
+pub mod queue;
+pub mod rounding;
 pub mod time;
+pub mod timesheet;
+pub mod watch;
 
-pub use jira::{submit_timelog, Error as JiraError, TimeLog};
-pub use meeting_types::{Meeting, MeetingType, Project};
-pub use preferences::{PrefRef, Preferences, ReminderSettings, TimerState};
-pub use time::{string_to_seconds, Error as TimeParseError};
+pub use auth::AuthMode;
+pub use catalogs::{load_catalog, Error as CatalogError, SharedCatalog};
+pub use config::{org_config_path, parse_override, Error as ConfigError, Layer, OVERRIDABLE};
+pub use credentials::{CredentialStore, Secret};
+pub use jira::{Error as JiraError, IssueSummary, JiraSession, TimeLog, Worklog, WorklogUpdate};
+pub use journal::{
+    query_journal, recent_tickets, record, Frontend, JournalEntry, JournalQuery, Outcome,
+};
+pub use meeting_types::{catalog_problems, Meeting, Project};
+pub use paths::{config_file, CONFIG_VAR};
+pub use preferences::{LoadOptions, PrefRef, Preferences, ReminderSettings, TimerState};
+pub use profiles::{profile_problems, Profile};
+pub use queue::{
+    replay_queue, retry_queued, submit_or_queue, Queue, QueuedLog, ReplayReport, RetryError,
+    Submission,
+};
+pub use rounding::{Rounded, Rounding};
+pub use time::{
+    local_now, parse_duration, parse_started, parse_time, seconds_to_string, string_to_seconds,
+    week_of, Error as TimeParseError,
+};
+pub use timesheet::Timesheet;
+pub use watch::{watch_config, ConfigWatcher};
@@ -1,30 +1,98 @@
-use std::fmt;
-
 use serde::{Deserialize, Serialize};
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
-pub struct Meeting(pub MeetingType, pub String);
+use crate::rounding::Rounding;
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
-pub enum MeetingType {
-    Billable,
-    NonBillable,
-    Deployment,
-    Mentoring,
-    Recruitment,
-    PersonalDistraction,
+/// A ticket that time is regularly logged to, such as a project's standups.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(from = "MeetingRecord")]
+pub struct Meeting {
+    pub label: String,
+    pub ticket: String,
+    /// Whether the time can be billed to a client, when that matters to the team.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub billable: Option<bool>,
+    /// The shared catalog the meeting came from, `None` for the user's own.
+    #[serde(skip)]
+    pub source: Option<String>,
 }
 
-impl fmt::Display for MeetingType {
-    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
-        write!(f, "{:?}", self)
+impl Meeting {
+    pub fn new(label: &str, ticket: &str) -> Self {
+        Meeting {
+            label: label.to_string(),
+            ticket: ticket.to_string(),
+            billable: None,
+            source: None,
+        }
     }
+
+    // Labels are matched loosely so `non-billable` finds "Non-Billable" and the old `NonBillable`
+    fn is_called(&self, label: &str) -> bool {
+        let simplify = |label: &str| {
+            label
+                .chars()
+                .filter(|c| c.is_alphanumeric())
+                .flat_map(char::to_lowercase)
+                .collect::<String>()
+        };
+
+        simplify(&self.label) == simplify(label)
+    }
+}
+
+// Configs written before meeting types could be edited store meetings as `["Billable", "PIM-1234"]`
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum MeetingRecord {
+    Current {
+        label: String,
+        ticket: String,
+        #[serde(default)]
+        billable: Option<bool>,
+    },
+    Legacy(String, String),
 }
 
+impl From<MeetingRecord> for Meeting {
+    fn from(record: MeetingRecord) -> Self {
+        match record {
+            MeetingRecord::Current {
+                label,
+                ticket,
+                billable,
+            } => Meeting {
+                label,
+                ticket,
+                billable,
+                source: None,
+            },
+            MeetingRecord::Legacy(kind, ticket) => {
+                let (label, billable) = match kind.as_str() {
+                    "Billable" => ("Billable", Some(true)),
+                    "NonBillable" => ("Non-Billable", Some(false)),
+                    "PersonalDistraction" => ("Personal Distraction", None),
+                    other => (other, None),
+                };
+
+                Meeting {
+                    label: label.to_string(),
+                    ticket,
+                    billable,
+                    source: None,
+                }
+            }
+        }
+    }
+}
+
+/// A group of meetings, listed in the order they are shown.
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Project {
     pub name: String,
     pub meetings: Vec<Meeting>,
+    /// Overrides the rounding in `Preferences` for this project's tickets.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub rounding: Option<Rounding>,
 }
 
 impl Project {
@@ -32,42 +100,141 @@ impl Project {
         Project {
             name: name.to_string(),
             meetings,
+            rounding: None,
         }
     }
+
+    /// The meeting with the given label, ignoring case, spaces and punctuation.
+    pub fn meeting(&self, label: &str) -> Option<&Meeting> {
+        self.meetings
+            .iter()
+            .find(|meeting| meeting.is_called(label))
+    }
+
+    // A ticket belongs to the project when it is one of its meetings or shares its key, so `PIM`
+    // covers `PIM-1234`
+    pub(crate) fn owns(&self, ticket: &str) -> bool {
+        let key = ticket.split('-').next().unwrap_or_default();
+
+        self.name.eq_ignore_ascii_case(key)
+            || self
+                .meetings
+                .iter()
+                .any(|meeting| meeting.ticket.eq_ignore_ascii_case(ticket))
+    }
 }
 
-pub fn seed_meeting_tickets() -> Vec<Project> {
-    vec![
-        Project::new(
-            "PIM",
+/// Mistakes in a catalog that would make meetings impossible to pick, such as a project with no
+/// name or two meetings sharing a label.
+pub fn catalog_problems(projects: &[Project]) -> Vec<String> {
+    let mut problems = Vec::new();
+
+    for (i, project) in projects.iter().enumerate() {
+        if project.name.trim().is_empty() {
+            problems.push(format!("Project {} has no name", i + 1));
+        } else if projects[..i]
+            .iter()
+            .any(|other| other.name.eq_ignore_ascii_case(&project.name))
+        {
+            problems.push(format!("There is more than one `{}` project", project.name));
+        }
+
+        for (j, meeting) in project.meetings.iter().enumerate() {
+            if meeting.label.trim().is_empty() {
+                problems.push(format!(
+                    "Meeting {} in `{}` has no label",
+                    j + 1,
+                    project.name
+                ));
+            } else if project.meetings[..j]
+                .iter()
+                .any(|other| other.is_called(&meeting.label))
+            {
+                problems.push(format!(
+                    "`{}` has more than one `{}` meeting",
+                    project.name, meeting.label
+                ));
+            }
+
+            if meeting.ticket.trim().is_empty() {
+                problems.push(format!(
+                    "`{}` in `{}` has no ticket",
+                    meeting.label, project.name
+                ));
+            }
+        }
+    }
+
+    problems
+}
+
+#[cfg(test)]
+mod test {
+    use super::{catalog_problems, Meeting, Project};
+
+    #[test]
+    fn migrates_legacy_meetings() {
+        let project: Project = serde_json::from_str(
+            r#"{"name": "PIM", "meetings": [["Billable", "PIM-1"], ["NonBillable", "PTD-2"], ["Deployment", "PIM-3"]]}"#,
+        )
+        .unwrap();
+
+        assert_eq!(
+            project.meetings,
             vec![
-                Meeting(MeetingType::Billable, "PIM-6126".to_string()),
-                Meeting(MeetingType::NonBillable, "PTD-1528".to_string()),
-                Meeting(MeetingType::Deployment, "PIM-6155".to_string()),
-            ],
-        ),
-        Project::new(
-            "PP",
+                Meeting {
+                    billable: Some(true),
+                    ..Meeting::new("Billable", "PIM-1")
+                },
+                Meeting {
+                    billable: Some(false),
+                    ..Meeting::new("Non-Billable", "PTD-2")
+                },
+                Meeting::new("Deployment", "PIM-3"),
+            ]
+        );
+
+        let saved = serde_json::to_string(&project).unwrap();
+        let reloaded: Project = serde_json::from_str(&saved).unwrap();
+        assert_eq!(reloaded.meetings, project.meetings);
+    }
+
+    #[test]
+    fn finds_meetings_by_loose_label() {
+        let project = Project::new(
+            "PIM",
             vec![
-                Meeting(MeetingType::Billable, "PP-6349".to_string()),
-                Meeting(MeetingType::NonBillable, "PTD-1527".to_string()),
-                Meeting(MeetingType::Deployment, "PP-6369".to_string()),
+                Meeting::new("Non-Billable", "PTD-2"),
+                Meeting::new("Sprint Review", "PIM-4"),
             ],
-        ),
-        Project::new(
-            "PAP",
-            vec![Meeting(MeetingType::Billable, "PAP-2728".to_string())],
-        ),
-        Project::new(
-            "BED",
+        );
+
+        assert_eq!(project.meeting("nonbillable").unwrap().ticket, "PTD-2");
+        assert_eq!(project.meeting("sprint-review").unwrap().ticket, "PIM-4");
+        assert!(project.meeting("standup").is_none());
+    }
+
+    #[test]
+    fn reports_catalog_problems() {
+        let projects = vec![
+            Project::new(
+                "PIM",
+                vec![
+                    Meeting::new("Standup", "PIM-1"),
+                    Meeting::new("stand-up", ""),
+                ],
+            ),
+            Project::new("pim", Vec::new()),
+        ];
+
+        assert_eq!(
+            catalog_problems(&projects),
             vec![
-                Meeting(MeetingType::Billable, "BED-4443".to_string()),
-                Meeting(MeetingType::NonBillable, "PTD-1551".to_string()),
-            ],
-        ),
-        Project::new(
-            "PTB",
-            vec![Meeting(MeetingType::Billable, "PTB-19990".to_string())],
-        ),
-    ]
+                "`PIM` has more than one `stand-up` meeting",
+                "`stand-up` in `PIM` has no ticket",
+                "There is more than one `pim` project",
+            ]
+        );
+        assert!(catalog_problems(&[]).is_empty());
+    }
 }
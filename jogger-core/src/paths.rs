@@ -0,0 +1,125 @@
+use std::ffi::OsString;
+use std::io::{Error, ErrorKind};
+use std::path::{Path, PathBuf};
+
+const CONFIG_FILENAME: &str = "jogger.conf";
+
+/// Environment variable naming an alternate config file.
+pub const CONFIG_VAR: &str = "JOGGER_CONFIG";
+
+/// The user's config file: `explicit` when given, then `$JOGGER_CONFIG`, then `jogger.conf` in
+/// `$XDG_CONFIG_HOME` or `~/.config`.
+pub fn config_file(explicit: Option<&Path>) -> Result<PathBuf, Error> {
+    if let Some(path) = explicit {
+        return Ok(path.to_path_buf());
+    }
+
+    match std::env::var_os(CONFIG_VAR) {
+        Some(path) if !path.is_empty() => Ok(PathBuf::from(path)),
+        _ => moved_from_legacy(
+            base_dir("XDG_CONFIG_HOME", ".config")?.join(CONFIG_FILENAME),
+            CONFIG_FILENAME,
+        ),
+    }
+}
+
+/// A file that jogger keeps for itself, like the timer or the journal, under `$XDG_STATE_HOME`.
+/// `legacy` is its name from when it lived next to the config, if it did.
+pub(crate) fn state_file(name: &str, legacy: Option<&str>) -> Result<PathBuf, Error> {
+    let path = base_dir("XDG_STATE_HOME", ".local/state")?
+        .join("jogger")
+        .join(name);
+    match legacy {
+        Some(legacy) => moved_from_legacy(path, legacy),
+        None => Ok(path),
+    }
+}
+
+/// A file that can be fetched again if it's lost, under `$XDG_CACHE_HOME`.
+pub(crate) fn cache_file(name: &str, legacy: &str) -> Result<PathBuf, Error> {
+    let dir = base_dir("XDG_CACHE_HOME", ".cache")?.join("jogger");
+    moved_from_legacy(dir.join(name), legacy)
+}
+
+/// Creates the directory `path` goes in.
+pub(crate) fn create_parent(path: &Path) -> Result<(), Error> {
+    match path.parent() {
+        Some(dir) if !dir.as_os_str().is_empty() => std::fs::create_dir_all(dir),
+        _ => Ok(()),
+    }
+}
+
+fn base_dir(var: &str, fallback: &str) -> Result<PathBuf, Error> {
+    resolve_base_dir(std::env::var_os(var), dirs::home_dir(), var, fallback)
+}
+
+// The XDG spec says relative paths are to be ignored. Without a home directory there is nowhere
+// sensible to fall back to, and the working directory is the last place settings should end up.
+fn resolve_base_dir(
+    xdg: Option<OsString>,
+    home: Option<PathBuf>,
+    var: &str,
+    fallback: &str,
+) -> Result<PathBuf, Error> {
+    match xdg.map(PathBuf::from) {
+        Some(dir) if dir.is_absolute() => Ok(dir),
+        _ => home
+            .filter(|home| home.is_absolute())
+            .map(|home| home.join(fallback))
+            .ok_or_else(|| {
+                Error::new(
+                    ErrorKind::NotFound,
+                    format!("no home directory was found, set {var} or {CONFIG_VAR}"),
+                )
+            }),
+    }
+}
+
+// Everything used to live in ~/.config, so files still there are moved on first use
+fn moved_from_legacy(path: PathBuf, legacy: &str) -> Result<PathBuf, Error> {
+    let Some(old) = dirs::home_dir().map(|home| home.join(".config").join(legacy)) else {
+        return Ok(path);
+    };
+    if path.exists() || old == path || !old.is_file() {
+        return Ok(path);
+    }
+
+    create_parent(&path)?;
+    std::fs::rename(&old, &path)
+        .or_else(|_| std::fs::copy(&old, &path).and_then(|_| std::fs::remove_file(&old)))?;
+
+    Ok(path)
+}
+
+#[cfg(test)]
+mod test {
+    use super::resolve_base_dir;
+    use std::path::PathBuf;
+
+    #[test]
+    fn xdg_directories_win_unless_relative() {
+        let home = || Some(PathBuf::from("/home/bee"));
+        let resolve = |xdg: Option<&str>| {
+            resolve_base_dir(xdg.map(Into::into), home(), "XDG_CONFIG_HOME", ".config").unwrap()
+        };
+
+        assert_eq!(resolve(None), PathBuf::from("/home/bee/.config"));
+        assert_eq!(resolve(Some("/sync/config")), PathBuf::from("/sync/config"));
+        assert_eq!(resolve(Some("config")), PathBuf::from("/home/bee/.config"));
+    }
+
+    #[test]
+    fn no_home_is_an_error_not_the_working_directory() {
+        assert!(resolve_base_dir(None, None, "XDG_STATE_HOME", ".local/state").is_err());
+        assert!(
+            resolve_base_dir(None, Some(PathBuf::new()), "XDG_STATE_HOME", ".local/state").is_err()
+        );
+        assert!(resolve_base_dir(
+            Some("/state".into()),
+            None,
+            "XDG_STATE_HOME",
+            ".local/state"
+        )
+        .is_ok());
+    }
+}
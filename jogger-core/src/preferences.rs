@@ -1,16 +1,33 @@
 use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::borrow::Cow;
 
 use std::io::Error;
+use std::path::{Path, PathBuf};
 use std::{cell::RefCell, rc::Rc};
 use time::OffsetDateTime;
 
-use crate::meeting_types::{seed_meeting_tickets, Project};
+use crate::auth::AuthMode;
+use crate::catalogs::{self, cached_catalogs, SharedCatalog};
+use crate::config::{
+    cli_layer, env_layers, merge_settings, org_config_path, read_settings, resolve,
+    Error as ConfigError, Layer, Origins, Settings, CONFIG_VERSION,
+};
+use crate::credentials::{self, Credential, Secret};
+use crate::files;
+use crate::jira::{JiraSession, Worklog};
+use crate::meeting_types::Project;
+use crate::paths::{config_file, state_file};
+use crate::profiles::Profile;
+use crate::rounding::{Rounded, Rounding};
+use crate::time::{parse_duration, parse_time, Error as TimeParseError, DEFAULT_WORKDAY_SECONDS};
 
-const PREF_FILENAME: &str = "jogger.conf";
+const TIMER_FILENAME: &str = "timer.json";
 
 pub type PrefRef = Rc<RefCell<Preferences>>;
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
 pub struct ReminderSettings {
     pub enabled: bool,
     pub interval_minutes: u32, // 15, 30, or 60
@@ -26,74 +43,224 @@ impl Default for ReminderSettings {
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize, Default)]
+#[serde(default)]
 pub struct TimerState {
     pub last_log_time: Option<i64>, // Unix timestamp
     pub accumulated_seconds: u32,
     pub last_ticket: Option<String>,
     pub last_log_date: Option<String>, // YYYY-MM-DD for daily reset
+    #[serde(default)]
+    pub last_worklog_id: Option<String>, // Jira id of the most recent worklog, for undo
 }
 
+/// Where to read settings from, as given on the command line.
+#[derive(Debug, Clone, Default)]
+pub struct LoadOptions {
+    /// A config file to use instead of the usual one.
+    pub config: Option<PathBuf>,
+    /// `key=value` settings replacing those from every file.
+    pub overrides: Vec<(String, String)>,
+}
+
+// Every setting has a default so org defaults and the user's file only need the ones they change
 #[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
 pub struct Preferences {
+    version: u64, // Layout of the file, see `config::MIGRATIONS`
     pub name: String,
     pub email: String,
-    pub api_key: String,
+    pub api_key: Secret, // Saved to a credential store, see `save`
+    #[serde(default)]
+    pub auth_mode: AuthMode,
     pub jira_url: String,
+    #[serde(default)]
     pub custom_meetings: Vec<Project>,
+    /// Files or URLs of shared catalogs merged into `custom_meetings`, see [`Preferences::meetings`].
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub catalog_sources: Vec<String>,
+    #[serde(skip)]
+    pub shared_catalogs: Vec<SharedCatalog>,
+    /// Other Jira sites, see [`Preferences::for_ticket`].
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub profiles: Vec<Profile>,
+    #[serde(skip)]
+    origins: Origins,
+    #[serde(skip)]
+    path: Option<PathBuf>, // The file this was loaded from, and is saved to
+    #[serde(skip)]
+    stored_keys: Vec<(Credential, Secret)>, // What's in credential stores, so it isn't saved again
+    #[serde(skip)]
+    key_errors: Vec<credentials::Error>,
+    #[serde(skip)]
+    saved: Settings, // The user's file as last read or written, to tell what changed since
     pub reminder_settings: ReminderSettings,
-    pub timer_state: TimerState,
+    #[serde(skip)]
+    pub timer_state: TimerState, // Kept in its own file so the config stays the same between logs
+    #[serde(default = "default_workday_hours")]
+    pub workday_hours: f64, // How long `1d` is
+    #[serde(default)]
+    pub rounding: Rounding,
+}
+
+fn default_workday_hours() -> f64 {
+    DEFAULT_WORKDAY_SECONDS as f64 / 3600.0
 }
 
 impl Preferences {
     pub fn new() -> Self {
         Preferences {
+            version: CONFIG_VERSION,
             name: String::new(),
             email: String::new(),
-            api_key: String::new(),
+            api_key: Secret::default(),
+            auth_mode: AuthMode::default(),
             jira_url: String::new(),
-            custom_meetings: seed_meeting_tickets(),
+            custom_meetings: Vec::new(),
+            catalog_sources: Vec::new(),
+            shared_catalogs: Vec::new(),
+            profiles: Vec::new(),
+            origins: Origins::default(),
+            path: None,
+            stored_keys: Vec::new(),
+            key_errors: Vec::new(),
+            saved: Settings::new(),
             reminder_settings: ReminderSettings::default(),
             timer_state: TimerState::default(),
+            workday_hours: default_workday_hours(),
+            rounding: Rounding::default(),
         }
     }
 
-    pub fn load() -> Result<Self, Error> {
-        let input = std::fs::read_to_string(
-            dirs::home_dir()
-                .unwrap_or_default()
-                .join(".config")
-                .join(PREF_FILENAME),
-        )?;
-
-        let prefs = match serde_json::from_str::<Preferences>(&input) {
-            Ok(p) => p,
-            Err(_) => {
-                let mut prefs = Preferences::new();
-                for line in input.lines() {
-                    if let Some((key, value)) = line.split_once('=') {
-                        match key {
-                            "NAME" => prefs.set_name(value),
-                            "API_KEY" => prefs.set_api_key(value),
-                            _ => &mut prefs,
-                        };
-                    }
-                }
-                Self::backup().ok();
-                prefs.save()?;
-                prefs
+    pub fn load() -> Result<Self, ConfigError> {
+        Self::load_with(&LoadOptions::default())
+    }
+
+    /// Loads the organisation defaults, the user's config, `JOGGER_*` environment variables and
+    /// finally overrides from the command line, each replacing settings from the ones before.
+    /// Nothing is changed on disk when a file can't be read.
+    pub fn load_with(options: &LoadOptions) -> Result<Self, ConfigError> {
+        let org_path = org_config_path();
+        let user_path = config_file(options.config.as_deref())
+            .map_err(|e| ConfigError::Invalid(e.to_string()))?;
+
+        let (org, _) = read_settings(&org_path)?;
+        // Held until any upgrade is written, loading still works where the lock can't be created
+        let lock = files::lock(&user_path).ok();
+        let (mut user, upgraded) = read_settings(&user_path)?;
+
+        // Keys saved in plain text, by older versions or by hand, are moved to a credential
+        // store, and stay where they are if that fails
+        let mut saved = user.clone();
+        let mut stored_keys = Vec::new();
+        let moved = credentials::store_keys(&mut saved, &mut stored_keys).unwrap_or(false);
+
+        if upgraded || moved {
+            // Keep the original around, unless that would leave a key behind in plain text, and
+            // carry on in memory if the changes can't be saved
+            if !moved {
+                Self::backup(&user_path).ok();
             }
-        };
+            if let Ok(saved) = serde_json::to_string_pretty(&saved) {
+                files::write_atomic(&user_path, &saved).ok();
+            }
+        }
+        drop(lock);
+
+        // The file only says where keys are, fetch them so they layer like any other setting. A
+        // key that can't be fetched is left empty so the user can still get to Setup.
+        let (fetched, key_errors) =
+            credentials::fetch_keys(&mut user, &[]).map_err(ConfigError::Invalid)?;
+        stored_keys.extend(fetched);
+
+        // The timer used to be saved with the settings, and is picked up from there until it has
+        // a file of its own
+        let legacy_timer = user.remove("timer_state");
+
+        let mut layers = vec![
+            (Layer::Org(org_path), org),
+            (Layer::User(user_path.clone()), user),
+        ];
+        layers.extend(env_layers(std::env::vars()).map_err(ConfigError::Invalid)?);
+        layers.push((
+            Layer::Cli,
+            cli_layer(&options.overrides).map_err(ConfigError::Invalid)?,
+        ));
+
+        let (config, origins) = resolve(layers);
+        let mut prefs: Preferences = serde_json::from_value(Value::Object(config))
+            .map_err(|e| ConfigError::Invalid(e.to_string()))?;
+        prefs.origins = origins;
+        prefs.path = Some(user_path);
+        prefs.stored_keys = stored_keys;
+        prefs.key_errors = key_errors;
+        prefs.saved = saved;
+        prefs.timer_state = load_timer()
+            .or_else(|| legacy_timer.and_then(|timer| serde_json::from_value(timer).ok()))
+            .unwrap_or_default();
+        prefs.shared_catalogs = cached_catalogs()
+            .into_iter()
+            .filter(|catalog| prefs.catalog_sources.contains(&catalog.source))
+            .collect();
 
         Ok(prefs)
     }
 
+    /// Why API keys in the config couldn't be read from their credential store. They are left
+    /// empty, and stay where they are until new ones are saved.
+    pub fn key_errors(&self) -> &[credentials::Error] {
+        &self.key_errors
+    }
+
+    /// What to tell the user about [`Preferences::key_errors`], if there are any.
+    pub fn describe_key_errors(&self) -> Option<String> {
+        if self.key_errors.is_empty() {
+            return None;
+        }
+
+        let errors: Vec<String> = self.key_errors.iter().map(|e| e.to_string()).collect();
+        Some(format!(
+            "Your API key could not be read:\n\n{}\n\nEnter it again in the settings to keep using Jira.",
+            errors.join("\n")
+        ))
+    }
+
+    /// Which layer the current value of the setting `key` came from.
+    pub fn origin(&self, key: &str) -> Layer {
+        self.origins.of(key)
+    }
+
+    /// Every setting with a short description of its value and where it came from, with the API
+    /// key hidden.
+    pub fn settings(&self) -> Vec<(String, String, Layer)> {
+        let Ok(Value::Object(config)) = serde_json::to_value(self) else {
+            return Vec::new();
+        };
+
+        config
+            .into_iter()
+            .filter(|(key, _)| key != "version")
+            .map(|(key, value)| {
+                let value = match value {
+                    Value::String(secret) if key == "api_key" && !secret.is_empty() => {
+                        "********".to_string()
+                    }
+                    Value::String(value) => value,
+                    Value::Array(items) => format!("{} item(s)", items.len()),
+                    value => value.to_string(),
+                };
+                let origin = self.origin(&key);
+                (key, value, origin)
+            })
+            .collect()
+    }
+
     pub fn set_name(&mut self, name: &str) -> &mut Self {
         self.name = name.to_string();
         self
     }
 
     pub fn set_api_key(&mut self, api_key: &str) -> &mut Self {
-        self.api_key = api_key.to_string();
+        self.api_key = Secret::new(api_key);
         self
     }
 
@@ -102,28 +269,221 @@ impl Preferences {
         self
     }
 
+    pub fn set_auth_mode(&mut self, auth_mode: AuthMode) -> &mut Self {
+        self.auth_mode = auth_mode;
+        self
+    }
+
     pub fn set_jira_url(&mut self, jira_url: &str) -> &mut Self {
         self.jira_url = jira_url.to_string();
         self
     }
 
-    pub fn save(&self) -> Result<(), Error> {
-        let path = dirs::home_dir().unwrap_or_default().join(".config");
-        std::fs::create_dir_all(&path)?;
-        std::fs::write(
-            path.join(PREF_FILENAME),
-            serde_json::to_string_pretty(self)?,
-        )?;
+    pub fn set_workday_hours(&mut self, workday_hours: f64) -> &mut Self {
+        self.workday_hours = workday_hours;
+        self
+    }
+
+    pub fn set_rounding(&mut self, rounding: Rounding) -> &mut Self {
+        self.rounding = rounding;
+        self
+    }
+
+    /// The profile `ticket` is logged to, or `None` for the main site.
+    pub fn profile_for(&self, ticket: &str) -> Option<&Profile> {
+        self.profiles.iter().find(|profile| profile.routes(ticket))
+    }
 
+    /// `ticket`, followed by the name of the profile it's logged to unless that's the main site.
+    pub fn describe_ticket(&self, ticket: &str) -> String {
+        match self.profile_for(ticket) {
+            Some(profile) => format!("{ticket} on {}", profile.name),
+            None => ticket.to_string(),
+        }
+    }
+
+    /// A Jira session using a copy of these settings. Front ends make one when the settings are
+    /// loaded and keep it until they change, see [`JiraSession::with_preferences`].
+    pub fn session(&self) -> JiraSession {
+        JiraSession::new(self.clone())
+    }
+
+    /// These settings with the site and sign in of the profile `ticket` is routed to. Everything
+    /// that talks to Jira about a ticket goes through here.
+    pub fn for_ticket(&self, ticket: &str) -> Cow<'_, Preferences> {
+        match self.profile_for(ticket) {
+            Some(profile) => Cow::Owned(self.with_profile(profile)),
+            None => Cow::Borrowed(self),
+        }
+    }
+
+    /// The main site followed by every profile that has one, for what isn't about one ticket,
+    /// like searching.
+    pub fn sites(&self) -> Vec<Cow<'_, Preferences>> {
+        std::iter::once(Cow::Borrowed(self))
+            .chain(
+                self.profiles
+                    .iter()
+                    .filter(|profile| !profile.jira_url.trim().is_empty())
+                    .map(|profile| Cow::Owned(self.with_profile(profile))),
+            )
+            .collect()
+    }
+
+    fn with_profile(&self, profile: &Profile) -> Preferences {
+        Preferences {
+            jira_url: profile.jira_url.clone(),
+            email: profile.email.clone(),
+            api_key: profile.api_key.clone(),
+            auth_mode: profile.auth_mode,
+            ..self.clone()
+        }
+    }
+
+    /// The user's own meetings, including those of every profile, along with those from shared
+    /// catalogs as of the last refresh.
+    pub fn meetings(&self) -> Vec<Project> {
+        let own: Vec<Project> = self
+            .custom_meetings
+            .iter()
+            .chain(self.profiles.iter().flat_map(|p| &p.custom_meetings))
+            .cloned()
+            .collect();
+        catalogs::merge(&own, &self.shared_catalogs)
+    }
+
+    /// Fetches the shared catalogs again, returning the sources that could not be read.
+    pub fn refresh_catalogs(&mut self) -> Vec<catalogs::Error> {
+        let (catalogs, errors) = catalogs::refresh_catalogs(&self.catalog_sources);
+        self.shared_catalogs = catalogs;
+        errors
+    }
+
+    /// Lists `source` along with the `projects` just read from it, so it isn't fetched again.
+    pub fn add_catalog(
+        &mut self,
+        source: &str,
+        projects: Vec<Project>,
+    ) -> Result<(), catalogs::Error> {
+        self.catalog_sources.push(source.to_string());
+        self.shared_catalogs.push(SharedCatalog {
+            source: source.to_string(),
+            fetched: OffsetDateTime::now_utc().unix_timestamp(),
+            projects,
+        });
+        catalogs::write_cache(&self.shared_catalogs)
+    }
+
+    /// Stops using `source`, dropping its copy from the cache without fetching the others.
+    pub fn remove_catalog(&mut self, source: &str) -> Result<(), catalogs::Error> {
+        self.catalog_sources.retain(|listed| listed != source);
+        self.shared_catalogs
+            .retain(|catalog| catalog.source != source);
+        catalogs::write_cache(&self.shared_catalogs)
+    }
+
+    /// The rounding for `ticket`, preferring its project's over the global setting.
+    pub fn rounding_for(&self, ticket: &str) -> Rounding {
+        self.meetings()
+            .iter()
+            .filter(|project| project.owns(ticket))
+            .find_map(|project| project.rounding)
+            .unwrap_or(self.rounding)
+    }
+
+    /// How long a worklog of `seconds` on `ticket` will be once it has been rounded.
+    pub fn round(&self, ticket: &str, seconds: usize) -> Rounded {
+        Rounded {
+            raw: seconds,
+            rounded: self.rounding_for(ticket).apply(seconds),
+        }
+    }
+
+    /// Parses a duration like `1h30` or `2d`, with days as long as the configured workday.
+    pub fn parse_duration(&self, time: &str) -> Result<usize, TimeParseError> {
+        parse_duration(time, self.workday_seconds())
+    }
+
+    /// Parses a duration or a time range along with the date and start time, see [`parse_time`].
+    pub fn parse_time(
+        &self,
+        time: &str,
+        date: &str,
+        start: &str,
+    ) -> Result<(usize, Option<OffsetDateTime>), TimeParseError> {
+        parse_time(time, date, start, self.workday_seconds())
+    }
+
+    fn workday_seconds(&self) -> usize {
+        (self.workday_hours * 3600.0).round() as usize
+    }
+
+    /// Saves the timer, and the settings if they have changed, to the files they were loaded from.
+    /// The API key is put in the system's credential store, or an encrypted file without one.
+    ///
+    /// Other jogger processes may have saved in the meantime, so only the settings changed here
+    /// since loading replace what's in the file.
+    pub fn save(&mut self) -> Result<(), Error> {
+        let path = match &self.path {
+            Some(path) => path.clone(),
+            None => config_file(None)?,
+        };
+        self.save_settings(&path)?;
+
+        self.save_timer()
+    }
+
+    fn save_settings(&mut self, path: &Path) -> Result<(), Error> {
+        let mut config: Settings = serde_json::from_value(serde_json::to_value(&*self)?)?;
+        self.origins.restore(&mut config);
+
+        // Only references to keys are written, the keys themselves go to a credential store
+        credentials::store_keys(&mut config, &mut self.stored_keys).map_err(Error::other)?;
+
+        let lock = files::lock(path)?;
+        let (theirs, _) = read_settings(path).map_err(Error::other)?;
+        let merged = merge_settings(&self.saved, &config, theirs.clone());
+
+        // Leave the file alone when nothing changed, so synced dotfiles only move when settings do
+        if merged != theirs {
+            files::write_atomic(path, &serde_json::to_string_pretty(&merged)?)?;
+        }
+        drop(lock);
+
+        // Carry on with what was saved, including changes from elsewhere, so they aren't written
+        // back over the next time
+        let mut user = merged.clone();
+        let (stored_keys, key_errors) =
+            credentials::fetch_keys(&mut user, &self.stored_keys).map_err(Error::other)?;
+        user.remove("timer_state");
+        let (config, origins) = self.origins.with_user(path, user);
+        let reloaded: Preferences = serde_json::from_value(Value::Object(config))?;
+
+        *self = Preferences {
+            origins,
+            path: Some(path.to_path_buf()),
+            stored_keys,
+            key_errors,
+            saved: merged,
+            timer_state: std::mem::take(&mut self.timer_state),
+            shared_catalogs: std::mem::take(&mut self.shared_catalogs),
+            ..reloaded
+        };
         Ok(())
     }
 
-    fn backup() -> Result<(), Error> {
-        let path = dirs::home_dir().unwrap_or_default().join(".config");
-        std::fs::copy(
-            path.join(PREF_FILENAME),
-            path.join(format!("{PREF_FILENAME}.bak")),
-        )?;
+    /// Saves just the timer state. It's replaced as a whole rather than merged like the settings,
+    /// since its fields only make sense together and the newest save is the latest log anyway.
+    pub fn save_timer(&self) -> Result<(), Error> {
+        let path = state_file(TIMER_FILENAME, None)?;
+        let _lock = files::lock(&path)?;
+        files::write_atomic(&path, &serde_json::to_string_pretty(&self.timer_state)?)
+    }
+
+    fn backup(path: &Path) -> Result<(), Error> {
+        let mut backup = path.as_os_str().to_owned();
+        backup.push(".bak");
+        std::fs::copy(path, backup)?;
 
         Ok(())
     }
@@ -156,7 +516,8 @@ impl Preferences {
         false
     }
 
-    // Update timer state after logging
+    // Update timer state after logging. Whatever could be undone before is forgotten, since the
+    // log just made may not be a worklog yet.
     pub fn update_timer_state(&mut self, ticket: &str) {
         let now = OffsetDateTime::now_utc();
 
@@ -169,6 +530,13 @@ impl Preferences {
             now.day()
         ));
         self.timer_state.accumulated_seconds = 0; // Reset after logging
+        self.timer_state.last_worklog_id = None;
+    }
+
+    // Update timer state and remember the worklog so it can be undone
+    pub fn record_worklog(&mut self, worklog: &Worklog) {
+        self.update_timer_state(&worklog.ticket);
+        self.timer_state.last_worklog_id = Some(worklog.id.clone());
     }
 
     // Get elapsed time since last log
@@ -183,8 +551,90 @@ impl Preferences {
     }
 }
 
+fn load_timer() -> Option<TimerState> {
+    state_file(TIMER_FILENAME, None)
+        .and_then(std::fs::read_to_string)
+        .ok()
+        .and_then(|input| serde_json::from_str(&input).ok())
+}
+
 impl Default for Preferences {
     fn default() -> Self {
         Self::new()
     }
 }
+
+#[cfg(test)]
+mod test {
+    use super::{Preferences, CONFIG_VERSION};
+    use crate::config::{read_settings, resolve, Layer};
+    use crate::jira::Worklog;
+    use crate::rounding::Rounding;
+    use serde_json::Value;
+    use std::path::Path;
+    use time::OffsetDateTime;
+
+    // The settings in `path` alone, without the org config, environment or credential store
+    // that loading them for real would consult
+    fn read(path: &Path) -> Preferences {
+        let (user, _) = read_settings(path).unwrap();
+        let (config, origins) = resolve(vec![(Layer::User(path.to_path_buf()), user.clone())]);
+
+        let mut prefs: Preferences = serde_json::from_value(Value::Object(config)).unwrap();
+        prefs.origins = origins;
+        prefs.path = Some(path.to_path_buf());
+        prefs.saved = user;
+        prefs
+    }
+
+    #[test]
+    fn queued_logs_leave_nothing_to_undo() {
+        let mut prefs = Preferences::new();
+        prefs.record_worklog(&Worklog {
+            id: "10001".to_string(),
+            ticket: "PP-1".to_string(),
+            started: OffsetDateTime::UNIX_EPOCH,
+            seconds: 900,
+            comment: String::new(),
+        });
+        assert_eq!(prefs.timer_state.last_worklog_id.as_deref(), Some("10001"));
+
+        // Jira couldn't be reached, so the log was queued
+        prefs.update_timer_state("PP-1");
+        assert_eq!(prefs.timer_state.last_ticket.as_deref(), Some("PP-1"));
+        assert_eq!(prefs.timer_state.last_worklog_id, None);
+    }
+
+    #[test]
+    fn saving_twice_keeps_changes_from_other_processes() {
+        let dir = std::env::temp_dir().join(format!("jogger-preferences-{}", std::process::id()));
+        let path = dir.join("jogger.conf");
+        std::fs::create_dir_all(&dir).unwrap();
+        std::fs::write(
+            &path,
+            format!(r#"{{"version": {CONFIG_VERSION}, "name": "Bee", "email": "bee@test"}}"#),
+        )
+        .unwrap();
+
+        let mut mine = read(&path);
+        let mut theirs = read(&path);
+
+        theirs.email = "bea@test".to_string();
+        theirs.save_settings(&path).unwrap();
+
+        mine.name = "Bea".to_string();
+        mine.save_settings(&path).unwrap();
+        assert_eq!(mine.email, "bea@test");
+
+        mine.rounding = Rounding::PRESETS[1];
+        mine.save_settings(&path).unwrap();
+
+        let saved = read(&path);
+        assert_eq!(
+            (saved.name.as_str(), saved.email.as_str()),
+            ("Bea", "bea@test")
+        );
+        assert_eq!(saved.rounding, Rounding::PRESETS[1]);
+        std::fs::remove_dir_all(dir).ok();
+    }
+}
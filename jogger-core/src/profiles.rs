@@ -0,0 +1,115 @@
+use serde::{Deserialize, Serialize};
+
+use crate::auth::AuthMode;
+use crate::credentials::Secret;
+use crate::meeting_types::Project;
+
+/// Another Jira site with its own sign in and meetings. Tickets whose keys start with one of
+/// `prefixes` are logged there instead of to the main site.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Profile {
+    pub name: String,
+    /// Project keys such as `ACME`, optionally written `ACME-*`.
+    pub prefixes: Vec<String>,
+    pub jira_url: String,
+    pub email: String,
+    pub api_key: Secret, // Saved to a credential store like the main one
+    pub auth_mode: AuthMode,
+    pub custom_meetings: Vec<Project>,
+}
+
+impl Profile {
+    /// Whether `ticket` belongs to one of this profile's projects.
+    pub fn routes(&self, ticket: &str) -> bool {
+        let project = ticket.trim().split('-').next().unwrap_or_default();
+        self.prefixes.iter().any(|prefix| {
+            let prefix = prefix.trim().trim_end_matches('*').trim_end_matches('-');
+            !prefix.is_empty() && prefix.eq_ignore_ascii_case(project)
+        })
+    }
+}
+
+/// Problems that would make tickets go to the wrong place: profiles without a name or site, and
+/// prefixes claimed by more than one profile.
+pub fn profile_problems(profiles: &[Profile]) -> Vec<String> {
+    let mut problems = Vec::new();
+
+    for (index, profile) in profiles.iter().enumerate() {
+        let name = match profile.name.trim() {
+            "" => {
+                problems.push(format!("Profile {} has no name", index + 1));
+                format!("Profile {}", index + 1)
+            }
+            name => name.to_string(),
+        };
+        if profile.jira_url.trim().is_empty() {
+            problems.push(format!("{name} has no Jira URL"));
+        }
+        if profile.prefixes.is_empty() {
+            problems.push(format!(
+                "{name} has no ticket prefixes, so nothing is logged to it"
+            ));
+        }
+
+        for prefix in &profile.prefixes {
+            let ticket = format!(
+                "{}-1",
+                prefix.trim().trim_end_matches('*').trim_end_matches('-')
+            );
+            if let Some(other) = profiles[..index].iter().find(|other| other.routes(&ticket)) {
+                problems.push(format!(
+                    "{prefix} is claimed by both {} and {name}, {} is used",
+                    other.name, other.name
+                ));
+            }
+        }
+    }
+
+    problems
+}
+
+#[cfg(test)]
+mod test {
+    use super::{profile_problems, Profile};
+    use crate::preferences::Preferences;
+
+    fn profile(name: &str, prefixes: &[&str]) -> Profile {
+        Profile {
+            name: name.to_string(),
+            prefixes: prefixes.iter().map(|prefix| prefix.to_string()).collect(),
+            jira_url: format!("https://{name}.test"),
+            ..Profile::default()
+        }
+    }
+
+    #[test]
+    fn tickets_route_by_project_key() {
+        let acme = profile("acme", &["ACME-*", "ops"]);
+
+        assert!(acme.routes("ACME-12"));
+        assert!(acme.routes("acme-1"));
+        assert!(acme.routes("OPS-7"));
+        assert!(!acme.routes("ACMEX-1"));
+        assert!(!acme.routes("PTD-3"));
+    }
+
+    #[test]
+    fn preferences_route_tickets_to_profiles() {
+        let mut prefs = Preferences::new();
+        prefs.jira_url = "https://ours.test".to_string();
+        prefs.profiles = vec![profile("acme", &["ACME"]), profile("unused", &[])];
+        prefs.profiles[1].jira_url.clear();
+
+        assert_eq!(prefs.for_ticket("ACME-1").jira_url, "https://acme.test");
+        assert_eq!(prefs.for_ticket("PTD-1").jira_url, "https://ours.test");
+        assert_eq!(prefs.sites().len(), 2);
+    }
+
+    #[test]
+    fn overlapping_prefixes_are_problems() {
+        let profiles = vec![profile("acme", &["ACME"]), profile("other", &["acme-*"])];
+        assert_eq!(profile_problems(&profiles).len(), 1);
+        assert!(profile_problems(&profiles[..1]).is_empty());
+    }
+}
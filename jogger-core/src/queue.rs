@@ -0,0 +1,421 @@
+use serde::{Deserialize, Serialize};
+use std::fmt;
+use std::io::{Error, ErrorKind};
+use std::sync::atomic::{AtomicU32, Ordering};
+use time::OffsetDateTime;
+
+use crate::files::{self, write_atomic};
+use crate::jira::{Error as JiraError, JiraSession, TimeLog, Worklog};
+use crate::journal::{self, Frontend, JournalEntry, Outcome};
+use crate::paths::state_file;
+
+const QUEUE_FILENAME: &str = "queue.json";
+const LEGACY_FILENAME: &str = "jogger.queue";
+
+/// A worklog that could not be submitted and is waiting to be retried.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct QueuedLog {
+    /// Stays the same however the rest of the queue changes, so an entry can be found again.
+    #[serde(default)]
+    pub id: String,
+    pub ticket_number: String,
+    pub time_spent_seconds: usize,
+    pub comment: String,
+    pub started: i64, // Unix timestamp
+    pub last_error: String,
+    #[serde(default)]
+    pub frontend: Frontend,
+}
+
+/// Worklogs waiting to be submitted, persisted next to jogger.conf.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct Queue {
+    pub entries: Vec<QueuedLog>,
+}
+
+pub enum Submission {
+    Submitted(Worklog),
+    /// Jira could not be reached, so the worklog was queued to be retried later.
+    Queued(JiraError),
+}
+
+#[derive(Debug, Default)]
+pub struct ReplayReport {
+    pub submitted: Vec<Worklog>,
+    pub remaining: usize,
+}
+
+/// Why a queued worklog could not be retried.
+#[derive(Debug)]
+pub enum RetryError {
+    /// It was submitted or discarded since the queue was loaded.
+    NotPending,
+    Jira(JiraError),
+    /// The queue file could not be read or saved.
+    Queue(Error),
+}
+
+impl fmt::Display for RetryError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            RetryError::NotPending => write!(f, "That worklog is no longer pending"),
+            RetryError::Jira(err) => err.fmt(f),
+            RetryError::Queue(err) => write!(f, "The queue could not be saved: {err}"),
+        }
+    }
+}
+
+impl std::error::Error for RetryError {}
+
+impl From<JiraError> for RetryError {
+    fn from(err: JiraError) -> Self {
+        RetryError::Jira(err)
+    }
+}
+
+/// Submits `log`, queueing it instead when the failure is one that retrying could fix.
+pub fn submit_or_queue(session: &JiraSession, log: &TimeLog) -> Result<Submission, JiraError> {
+    let started = log.started.unwrap_or_else(OffsetDateTime::now_utc);
+
+    let result = session.post_worklog(
+        &log.ticket_number,
+        log.time_spent_seconds,
+        &log.comment,
+        started,
+    );
+    let mut entry = JournalEntry::new(
+        log.frontend,
+        &log.ticket_number,
+        log.time_spent_seconds,
+        &log.comment,
+        started,
+        &result,
+    );
+
+    let submission = match result {
+        Ok(worklog) => Ok(Submission::Submitted(worklog)),
+        Err(err) if err.is_transient() => {
+            let queued = Queue::update(|queue| {
+                queue.entries.push(QueuedLog {
+                    id: new_id(),
+                    ticket_number: log.ticket_number.clone(),
+                    time_spent_seconds: log.time_spent_seconds,
+                    comment: log.comment.clone(),
+                    started: started.unix_timestamp(),
+                    last_error: err.to_string(),
+                    frontend: log.frontend,
+                })
+            });
+
+            match queued {
+                Ok(_) => {
+                    entry.outcome = Outcome::Queued;
+                    Ok(Submission::Queued(err))
+                }
+                Err(_) => Err(err),
+            }
+        }
+        Err(err) => Err(err),
+    };
+
+    journal::record(&entry).ok();
+    submission
+}
+
+impl Queue {
+    pub fn load() -> Result<Self, Error> {
+        let mut queue: Queue =
+            match std::fs::read_to_string(state_file(QUEUE_FILENAME, Some(LEGACY_FILENAME))?) {
+                Ok(input) => serde_json::from_str(&input)?,
+                Err(err) if err.kind() == ErrorKind::NotFound => Queue::default(),
+                Err(err) => return Err(err),
+            };
+
+        // Entries queued by older versions have no id, give them one that's the same every load
+        for (index, entry) in queue.entries.iter_mut().enumerate() {
+            if entry.id.is_empty() {
+                entry.id = format!("{}-{index}", entry.started);
+            }
+        }
+
+        Ok(queue)
+    }
+
+    /// Loads the queue, lets `change` modify it and saves the result. Other jogger processes
+    /// and threads wait until it's saved, so nothing they queue or submit meanwhile is lost or
+    /// submitted twice.
+    pub fn update<T>(change: impl FnOnce(&mut Queue) -> T) -> Result<T, Error> {
+        let path = state_file(QUEUE_FILENAME, Some(LEGACY_FILENAME))?;
+        let _lock = files::lock(&path)?;
+
+        let mut queue = Queue::load()?;
+        let result = change(&mut queue);
+        write_atomic(&path, &serde_json::to_string_pretty(&queue)?)?;
+
+        Ok(result)
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    /// The entry with `id`, if it's still pending.
+    pub fn get(&self, id: &str) -> Option<&QueuedLog> {
+        self.entries.iter().find(|entry| entry.id == id)
+    }
+
+    /// The entry with `id`, if it's still pending.
+    pub fn get_mut(&mut self, id: &str) -> Option<&mut QueuedLog> {
+        self.entries.iter_mut().find(|entry| entry.id == id)
+    }
+
+    /// Submits the entry with `id`, removing it from the queue if Jira accepts it.
+    pub fn retry(&mut self, id: &str, session: &JiraSession) -> Result<Worklog, RetryError> {
+        let index = self
+            .entries
+            .iter()
+            .position(|entry| entry.id == id)
+            .ok_or(RetryError::NotPending)?;
+
+        let worklog = submit_entry(&mut self.entries[index], session)?;
+        self.entries.remove(index);
+        Ok(worklog)
+    }
+
+    /// Retries every entry in order. Stops early once Jira looks unreachable again, leaving the
+    /// rest of the queue untouched.
+    pub fn replay(&mut self, session: &JiraSession) -> ReplayReport {
+        self.replay_with(|entry| submit_entry(entry, session))
+    }
+
+    fn replay_with(
+        &mut self,
+        mut submit: impl FnMut(&mut QueuedLog) -> Result<Worklog, JiraError>,
+    ) -> ReplayReport {
+        let mut report = ReplayReport::default();
+        let mut index = 0;
+
+        while let Some(entry) = self.entries.get_mut(index) {
+            match submit(entry) {
+                Ok(worklog) => {
+                    self.entries.remove(index);
+                    report.submitted.push(worklog);
+                }
+                Err(err) if err.is_transient() => break,
+                Err(_) => index += 1,
+            }
+        }
+
+        report.remaining = self.entries.len();
+        report
+    }
+
+    /// Carries the outcome of submitting from `tried`, a copy of this queue as it was loaded
+    /// before, over to it: entries that were submitted are removed and the rest keep the error
+    /// they met. Anything queued, edited or discarded meanwhile is left as it is.
+    fn settle(&mut self, loaded: &[String], tried: &Queue) {
+        self.entries
+            .retain(|entry| !loaded.contains(&entry.id) || tried.get(&entry.id).is_some());
+
+        for entry in &mut self.entries {
+            if let Some(tried) = tried.get(&entry.id) {
+                entry.last_error = tried.last_error.clone();
+            }
+        }
+    }
+}
+
+// Ids of the entries in `queue`, to tell afterwards which of them were submitted
+fn ids(queue: &Queue) -> Vec<String> {
+    queue.entries.iter().map(|entry| entry.id.clone()).collect()
+}
+
+/// Replays a copy of the queue against Jira, then removes the worklogs it submitted. The queue
+/// is only locked to save that, so other processes can keep queueing while Jira is slow.
+pub fn replay_queue(session: &JiraSession) -> Result<ReplayReport, Error> {
+    let mut tried = Queue::load()?;
+    if tried.is_empty() {
+        return Ok(ReplayReport::default());
+    }
+
+    let loaded = ids(&tried);
+    let mut report = tried.replay(session);
+    report.remaining = Queue::update(|queue| {
+        queue.settle(&loaded, &tried);
+        queue.entries.len()
+    })?;
+
+    Ok(report)
+}
+
+/// Submits the queued worklog with `id`, removing it from the queue if Jira accepts it. Like
+/// [`replay_queue`], the queue isn't locked while waiting on Jira.
+pub fn retry_queued(id: &str, session: &JiraSession) -> Result<Worklog, RetryError> {
+    let mut tried = Queue::load().map_err(RetryError::Queue)?;
+    let loaded = ids(&tried);
+
+    let result = tried.retry(id, session);
+    Queue::update(|queue| queue.settle(&loaded, &tried)).map_err(RetryError::Queue)?;
+
+    result
+}
+
+// Submits `entry`, recording the attempt in the journal and keeping the error on it if it fails
+fn submit_entry(entry: &mut QueuedLog, session: &JiraSession) -> Result<Worklog, JiraError> {
+    let started = OffsetDateTime::from_unix_timestamp(entry.started)
+        .unwrap_or_else(|_| OffsetDateTime::now_utc());
+
+    let result = session.post_worklog(
+        &entry.ticket_number,
+        entry.time_spent_seconds,
+        &entry.comment,
+        started,
+    );
+
+    let mut attempt = JournalEntry::new(
+        entry.frontend,
+        &entry.ticket_number,
+        entry.time_spent_seconds,
+        &entry.comment,
+        started,
+        &result,
+    );
+    if result.is_err() {
+        // It stays in the queue either way
+        attempt.outcome = Outcome::Queued;
+    }
+    journal::record(&attempt).ok();
+
+    if let Err(err) = &result {
+        entry.last_error = err.to_string();
+    }
+    result
+}
+
+// Unique within this process by the counter, and across processes by the time it was made
+fn new_id() -> String {
+    static COUNTER: AtomicU32 = AtomicU32::new(0);
+
+    format!(
+        "{:x}-{:x}-{}",
+        OffsetDateTime::now_utc().unix_timestamp_nanos(),
+        std::process::id(),
+        COUNTER.fetch_add(1, Ordering::Relaxed)
+    )
+}
+
+#[cfg(test)]
+mod test {
+    use super::{ids, new_id, Queue, QueuedLog, RetryError};
+    use crate::jira::{Error as JiraError, Worklog};
+    use crate::preferences::Preferences;
+    use time::OffsetDateTime;
+
+    fn queue(tickets: &[&str]) -> Queue {
+        Queue {
+            entries: tickets
+                .iter()
+                .map(|ticket| QueuedLog {
+                    id: new_id(),
+                    ticket_number: ticket.to_string(),
+                    time_spent_seconds: 60,
+                    comment: String::new(),
+                    started: 0,
+                    last_error: String::new(),
+                    frontend: Default::default(),
+                })
+                .collect(),
+        }
+    }
+
+    fn worklog(entry: &QueuedLog) -> Worklog {
+        Worklog {
+            id: entry.id.clone(),
+            ticket: entry.ticket_number.clone(),
+            started: OffsetDateTime::UNIX_EPOCH,
+            seconds: entry.time_spent_seconds,
+            comment: entry.comment.clone(),
+        }
+    }
+
+    fn tickets(queue: &Queue) -> Vec<&str> {
+        queue
+            .entries
+            .iter()
+            .map(|entry| entry.ticket_number.as_str())
+            .collect()
+    }
+
+    #[test]
+    fn replay_skips_rejected_worklogs_in_order() {
+        let mut queue = queue(&["A-1", "BAD-1", "A-2", "BAD-2", "A-3"]);
+        let mut attempts = Vec::new();
+
+        let report = queue.replay_with(|entry| {
+            attempts.push(entry.ticket_number.clone());
+            match entry.ticket_number.starts_with("BAD") {
+                true => Err(JiraError::Forbidden),
+                false => Ok(worklog(entry)),
+            }
+        });
+
+        assert_eq!(attempts, ["A-1", "BAD-1", "A-2", "BAD-2", "A-3"]);
+        let submitted: Vec<_> = report.submitted.iter().map(|w| &w.ticket).collect();
+        assert_eq!(submitted, ["A-1", "A-2", "A-3"]);
+        assert_eq!(tickets(&queue), ["BAD-1", "BAD-2"]);
+        assert_eq!(report.remaining, 2);
+    }
+
+    #[test]
+    fn replay_stops_once_jira_is_unreachable() {
+        let mut queue = queue(&["A-1", "A-2", "A-3"]);
+
+        let report = queue.replay_with(|entry| match entry.ticket_number.as_str() {
+            "A-2" => Err(JiraError::Network("offline".to_string())),
+            _ => Ok(worklog(entry)),
+        });
+
+        assert_eq!(report.submitted.len(), 1);
+        assert_eq!(tickets(&queue), ["A-2", "A-3"]);
+        assert_eq!(report.remaining, 2);
+    }
+
+    #[test]
+    fn settling_keeps_changes_made_while_submitting() {
+        let mut queue = queue(&["A-1", "BAD-1", "A-2", "BAD-2"]);
+        let loaded = ids(&queue);
+
+        let mut tried = queue.clone();
+        tried.replay_with(|entry| match entry.ticket_number.starts_with("BAD") {
+            true => {
+                entry.last_error = "rejected".to_string();
+                Err(JiraError::Forbidden)
+            }
+            false => Ok(worklog(entry)),
+        });
+
+        // Meanwhile BAD-1 was discarded and A-3 queued
+        queue.entries.remove(1);
+        queue.entries.extend(self::queue(&["A-3"]).entries);
+
+        queue.settle(&loaded, &tried);
+        assert_eq!(tickets(&queue), ["BAD-2", "A-3"]);
+        assert_eq!(queue.entries[0].last_error, "rejected");
+    }
+
+    #[test]
+    fn entries_are_found_by_id() {
+        let mut queue = queue(&["A-1", "A-2"]);
+        let id = queue.entries[1].id.clone();
+        assert_ne!(queue.entries[0].id, id);
+
+        queue.entries.remove(0);
+        assert_eq!(queue.get(&id).unwrap().ticket_number, "A-2");
+
+        let session = Preferences::new().session();
+        assert!(matches!(
+            queue.retry("gone", &session),
+            Err(RetryError::NotPending)
+        ));
+    }
+}
@@ -0,0 +1,174 @@
+use serde::{Deserialize, Serialize};
+use std::{fmt, str::FromStr};
+
+use crate::time::seconds_to_string;
+
+/// How logged durations are rounded before they are sent to Jira.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Rounding {
+    /// Send exactly what was entered.
+    #[default]
+    Exact,
+    /// Round up to the next multiple of `minutes`, e.g. for quarter-hour billing.
+    Up { minutes: u32 },
+    /// Round to the nearest multiple of `minutes`, but never down to nothing.
+    Nearest { minutes: u32 },
+}
+
+impl Rounding {
+    /// The choices offered in the setup screens.
+    pub const PRESETS: [Rounding; 6] = [
+        Rounding::Exact,
+        Rounding::Nearest { minutes: 5 },
+        Rounding::Nearest { minutes: 15 },
+        Rounding::Up { minutes: 5 },
+        Rounding::Up { minutes: 15 },
+        Rounding::Up { minutes: 30 },
+    ];
+
+    /// Rounds `seconds`, stopping at the largest duration there is rather than overflowing.
+    pub fn apply(&self, seconds: usize) -> usize {
+        match *self {
+            Rounding::Exact | Rounding::Up { minutes: 0 } | Rounding::Nearest { minutes: 0 } => {
+                seconds
+            }
+            Rounding::Up { minutes } => {
+                let step = minutes as usize * 60;
+                seconds.div_ceil(step).saturating_mul(step)
+            }
+            Rounding::Nearest { minutes } => {
+                let step = minutes as usize * 60;
+                (seconds.saturating_add(step / 2) / step)
+                    .max(1)
+                    .saturating_mul(step)
+            }
+        }
+    }
+}
+
+impl fmt::Display for Rounding {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Rounding::Exact => write!(f, "Don't round"),
+            Rounding::Up { minutes } => write!(f, "Round up to {minutes} minutes"),
+            Rounding::Nearest { minutes } => write!(f, "Round to the nearest {minutes} minutes"),
+        }
+    }
+}
+
+/// Parses the short form used in environment variables and flags: `exact`, `up:15` or `nearest:5`.
+impl FromStr for Rounding {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (kind, minutes) = s.trim().split_once(':').unwrap_or((s.trim(), ""));
+        let minutes = || {
+            minutes
+                .parse::<u32>()
+                .map_err(|_| format!("`{s}` needs a number of minutes, e.g. `{kind}:15`"))
+        };
+
+        match kind.to_lowercase().as_str() {
+            "exact" | "none" => Ok(Rounding::Exact),
+            "up" => Ok(Rounding::Up {
+                minutes: minutes()?,
+            }),
+            "nearest" => Ok(Rounding::Nearest {
+                minutes: minutes()?,
+            }),
+            _ => Err(format!(
+                "`{s}` is not a rounding, use `exact`, `up:<minutes>` or `nearest:<minutes>`"
+            )),
+        }
+    }
+}
+
+/// A duration as entered and as it will be logged.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Rounded {
+    pub raw: usize,
+    pub rounded: usize,
+}
+
+impl fmt::Display for Rounded {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        if self.raw == self.rounded {
+            write!(f, "{}", seconds_to_string(self.rounded))
+        } else {
+            write!(
+                f,
+                "{} (rounded from {})",
+                seconds_to_string(self.rounded),
+                seconds_to_string(self.raw)
+            )
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{Rounded, Rounding};
+    use crate::meeting_types::Project;
+    use crate::preferences::Preferences;
+
+    #[test]
+    fn rounds_up_to_quarter_hours() {
+        let quarter = Rounding::Up { minutes: 15 };
+        assert_eq!(quarter.apply(4063), 4500); // 1h 7m 43s
+        assert_eq!(quarter.apply(4500), 4500);
+        assert_eq!(quarter.apply(1), 900);
+    }
+
+    #[test]
+    fn rounds_to_nearest_but_not_to_zero() {
+        let five = Rounding::Nearest { minutes: 5 };
+        assert_eq!(five.apply(4063), 4200);
+        assert_eq!(five.apply(3700), 3600);
+        assert_eq!(five.apply(60), 300);
+        assert_eq!(Rounding::Exact.apply(4063), 4063);
+    }
+
+    #[test]
+    fn huge_durations_do_not_overflow() {
+        let quarter = Rounding::Up { minutes: 15 };
+        let five = Rounding::Nearest { minutes: 5 };
+        assert_eq!(quarter.apply(usize::MAX), usize::MAX);
+        assert_eq!(five.apply(usize::MAX), usize::MAX - usize::MAX % 300);
+        assert_eq!(
+            quarter.apply(usize::MAX - 900),
+            usize::MAX - usize::MAX % 900
+        );
+    }
+
+    #[test]
+    fn parses_short_form() {
+        assert_eq!("exact".parse(), Ok(Rounding::Exact));
+        assert_eq!("up:15".parse(), Ok(Rounding::Up { minutes: 15 }));
+        assert_eq!("Nearest:5".parse(), Ok(Rounding::Nearest { minutes: 5 }));
+        assert!("up".parse::<Rounding>().is_err());
+        assert!("sideways:5".parse::<Rounding>().is_err());
+    }
+
+    #[test]
+    fn shows_raw_and_rounded() {
+        let rounded = Rounded {
+            raw: 4063,
+            rounded: 4500,
+        };
+        assert_eq!(rounded.to_string(), "1h 15m (rounded from 1h 7m 43s)");
+    }
+
+    #[test]
+    fn projects_override_the_global_rounding() {
+        let mut billable = Project::new("PIM", Vec::new());
+        billable.rounding = Some(Rounding::Up { minutes: 15 });
+
+        let mut prefs = Preferences::new();
+        prefs.custom_meetings = vec![billable];
+        prefs.set_rounding(Rounding::Nearest { minutes: 5 });
+
+        assert_eq!(prefs.round("PIM-6216", 4063).rounded, 4500);
+        assert_eq!(prefs.round("PP-1", 4063).rounded, 4200);
+    }
+}
@@ -1,47 +1,496 @@
-use regex::{Match, Regex};
+use std::{fmt, ops::Range};
+use time::macros::format_description;
+use time::{Date, Duration, OffsetDateTime, PrimitiveDateTime, Time, UtcOffset, Weekday};
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Error {
+    message: String,
+    span: Option<Range<usize>>, // In characters
+}
 
-pub struct Error(String);
 impl Error {
+    fn new(message: String) -> Self {
+        Error {
+            message,
+            span: None,
+        }
+    }
+
+    fn at(message: String, span: Range<usize>) -> Self {
+        Error {
+            message,
+            span: Some(span),
+        }
+    }
+
     pub fn msg(&self) -> &str {
-        &self.0
+        &self.message
+    }
+
+    /// The characters of the input that were rejected, when the problem is with one part of it.
+    pub fn span(&self) -> Option<Range<usize>> {
+        self.span.clone()
+    }
+
+    /// The message followed by `input` with the rejected part underlined, for monospaced output.
+    pub fn describe(&self, input: &str) -> String {
+        match &self.span {
+            Some(span) => format!(
+                "{}\n  {input}\n  {}{}",
+                self.message,
+                " ".repeat(span.start),
+                "^".repeat(span.len().max(1))
+            ),
+            None => self.message.clone(),
+        }
+    }
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.message)
     }
 }
 
+impl std::error::Error for Error {}
+
+pub const DEFAULT_WORKDAY_SECONDS: usize = 8 * 60 * 60;
+
 const MINUTE: f64 = 60f64;
 const HOUR: f64 = 60f64 * 60f64;
 
+#[derive(Debug, Clone, Copy, PartialEq, PartialOrd)]
+enum Unit {
+    Second,
+    Minute,
+    Hour,
+    Day,
+    Week,
+}
+
+impl Unit {
+    fn parse(word: &str) -> Option<Unit> {
+        match word.to_lowercase().as_str() {
+            "s" | "sec" | "secs" | "second" | "seconds" => Some(Unit::Second),
+            "m" | "min" | "mins" | "minute" | "minutes" => Some(Unit::Minute),
+            "h" | "hr" | "hrs" | "hour" | "hours" => Some(Unit::Hour),
+            "d" | "day" | "days" => Some(Unit::Day),
+            "w" | "wk" | "wks" | "week" | "weeks" => Some(Unit::Week),
+            _ => None,
+        }
+    }
+
+    // Days and weeks are working time, not calendar time
+    fn seconds(self, workday: usize) -> f64 {
+        match self {
+            Unit::Second => 1.0,
+            Unit::Minute => MINUTE,
+            Unit::Hour => HOUR,
+            Unit::Day => workday as f64,
+            Unit::Week => 5.0 * workday as f64,
+        }
+    }
+
+    // The unit a bare number means when it follows this one, as in `1h30`. Seconds always have
+    // to be written out.
+    fn next(self) -> Option<Unit> {
+        match self {
+            Unit::Week => Some(Unit::Day),
+            Unit::Day => Some(Unit::Hour),
+            Unit::Hour => Some(Unit::Minute),
+            Unit::Minute | Unit::Second => None,
+        }
+    }
+}
+
+impl fmt::Display for Unit {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Unit::Second => write!(f, "seconds"),
+            Unit::Minute => write!(f, "minutes"),
+            Unit::Hour => write!(f, "hours"),
+            Unit::Day => write!(f, "days"),
+            Unit::Week => write!(f, "weeks"),
+        }
+    }
+}
+
+/// Parses a duration such as `1h30`, `1h 30m`, `1:30`, `1.5`, `1,5h`, `90 minutes` or `2d`, using
+/// the default eight hour workday for days and weeks.
 pub fn string_to_seconds(time: &str) -> Result<usize, Error> {
-    let r = Regex::new(r#"(?:(\d+)m)|(?:(\d+)h)(?:(\d*)m?)?|(?:(\d+\.?\d*)h?)"#).unwrap();
-    let result = r.captures(time);
+    parse_duration(time, DEFAULT_WORKDAY_SECONDS)
+}
+
+/// Parses a duration, counting a day as `workday` seconds and a week as five workdays.
+pub fn parse_duration(time: &str, workday: usize) -> Result<usize, Error> {
+    let chars: Vec<char> = time.chars().collect();
+    let mut cursor = Cursor {
+        chars: &chars,
+        pos: 0,
+    };
+
+    cursor.skip_whitespace();
+    if cursor.done() {
+        return Err(Error::new("No time was given".to_string()));
+    }
 
-    let mut seconds = 0;
+    if chars.contains(&':') {
+        return clock_duration(&mut cursor);
+    }
+
+    let mut seconds = 0f64;
+    let mut previous: Option<Unit> = None;
 
-    match result {
-        Some(result) => {
-            seconds += (parse_match(result.get(1)) * MINUTE).floor() as usize;
-            seconds += (parse_match(result.get(2)) * HOUR).floor() as usize;
-            seconds += (parse_match(result.get(3)) * MINUTE).floor() as usize;
-            seconds += (parse_match(result.get(4)) * HOUR).floor() as usize;
+    while !cursor.done() {
+        let start = cursor.pos;
+        let number = cursor.take_while(|c| c.is_ascii_digit() || c == '.' || c == ',');
+        if number.is_empty() {
+            let word = cursor.word(start);
+            return Err(Error::at(
+                format!("Expected a number but found `{}`", cursor.text(&word)),
+                word,
+            ));
         }
-        None => {
-            return Err(Error(format!(
-                "Time could not be parsed from string `{time}`"
-            )))
+
+        let value: f64 = number
+            .replace(',', ".")
+            .parse()
+            .map_err(|_| Error::at(format!("`{number}` is not a number"), start..cursor.pos))?;
+
+        cursor.skip_whitespace();
+        let unit_start = cursor.pos;
+        let word = cursor.take_while(char::is_alphabetic);
+
+        let unit = if word.is_empty() {
+            if !cursor.done() {
+                return Err(Error::at(
+                    format!("`{number}` needs a unit such as h or m"),
+                    start..start + number.chars().count(),
+                ));
+            }
+
+            match previous {
+                None => Unit::Hour,
+                Some(previous) => previous.next().ok_or_else(|| {
+                    Error::at(
+                        format!("`{number}` needs a unit, nothing is smaller than minutes"),
+                        start..cursor.pos,
+                    )
+                })?,
+            }
+        } else {
+            Unit::parse(&word).ok_or_else(|| {
+                Error::at(format!("Unknown unit `{word}`"), unit_start..cursor.pos)
+            })?
+        };
+
+        if let Some(previous) = previous.filter(|previous| unit >= *previous) {
+            return Err(Error::at(
+                format!("Unexpected {unit} after {previous}, give the largest units first"),
+                start..cursor.pos,
+            ));
+        }
+
+        // Anything from here on would not fit once rounded to whole seconds
+        seconds += value * unit.seconds(workday);
+        if !seconds.is_finite() || seconds >= usize::MAX as f64 {
+            let part = start..cursor.pos;
+            return Err(Error::at(
+                format!("`{}` is too long a time", cursor.text(&part)),
+                part,
+            ));
+        }
+        previous = Some(unit);
+        cursor.skip_whitespace();
+    }
+
+    match seconds.round() as usize {
+        0 => Err(Error::new("Time must be more than zero".to_string())),
+        seconds => Ok(seconds),
+    }
+}
+
+// `H:MM`, which has to be the whole input
+fn clock_duration(cursor: &mut Cursor) -> Result<usize, Error> {
+    let start = cursor.pos;
+    let hours = cursor.take_while(|c| c.is_ascii_digit());
+    if hours.is_empty() {
+        let word = cursor.word(start);
+        return Err(Error::at(
+            format!("Expected hours but found `{}`", cursor.text(&word)),
+            word,
+        ));
+    }
+
+    if cursor.take_while(|c| c == ':').len() != 1 {
+        return Err(Error::at(
+            "Expected hours and minutes like `1:30`".to_string(),
+            start..cursor.pos,
+        ));
+    }
+
+    let minutes_start = cursor.pos;
+    let minutes = cursor.take_while(|c| c.is_ascii_digit());
+    let minutes = match minutes.parse::<usize>() {
+        Ok(value) if minutes.len() == 2 && value < 60 => value,
+        _ => {
+            let word = cursor.word(minutes_start);
+            return Err(Error::at(
+                "Minutes must be two digits between 00 and 59".to_string(),
+                word,
+            ));
+        }
+    };
+
+    cursor.skip_whitespace();
+    if !cursor.done() {
+        let word = cursor.word(cursor.pos);
+        return Err(Error::at(
+            format!("Unexpected `{}` after the time", cursor.text(&word)),
+            word,
+        ));
+    }
+
+    let too_many = || {
+        Error::at(
+            format!("`{hours}` is too many hours"),
+            start..minutes_start - 1,
+        )
+    };
+    let seconds = hours
+        .parse::<usize>()
+        .ok()
+        .and_then(|hours| hours.checked_mul(3600))
+        .and_then(|seconds| seconds.checked_add(minutes * 60))
+        .ok_or_else(too_many)?;
+
+    match seconds {
+        0 => Err(Error::new("Time must be more than zero".to_string())),
+        seconds => Ok(seconds),
+    }
+}
+
+struct Cursor<'a> {
+    chars: &'a [char],
+    pos: usize,
+}
+
+impl Cursor<'_> {
+    fn done(&self) -> bool {
+        self.pos >= self.chars.len()
+    }
+
+    fn take_while<F: Fn(char) -> bool>(&mut self, predicate: F) -> String {
+        let start = self.pos;
+        while self.pos < self.chars.len() && predicate(self.chars[self.pos]) {
+            self.pos += 1;
+        }
+        self.chars[start..self.pos].iter().collect()
+    }
+
+    fn skip_whitespace(&mut self) {
+        self.take_while(char::is_whitespace);
+    }
+
+    // The run of non-whitespace starting at `start`, used to point at whatever was unexpected
+    fn word(&self, start: usize) -> Range<usize> {
+        let end = (start..self.chars.len())
+            .find(|&i| self.chars[i].is_whitespace())
+            .unwrap_or(self.chars.len());
+        start..end.max(start + 1)
+    }
+
+    fn text(&self, span: &Range<usize>) -> String {
+        self.chars[span.start.min(self.chars.len())..span.end.min(self.chars.len())]
+            .iter()
+            .collect()
+    }
+}
+
+/// Formats a duration the way [`string_to_seconds`] reads it, e.g. 5400 as `1h 30m`. Nothing
+/// is rounded, so reading it back gives the same number of seconds.
+pub fn seconds_to_string(seconds: usize) -> String {
+    let parts = [
+        (seconds / 3600, "h"),
+        (seconds / 60 % 60, "m"),
+        (seconds % 60, "s"),
+    ];
+
+    match parts
+        .iter()
+        .filter(|(value, _)| *value > 0)
+        .map(|(value, unit)| format!("{value}{unit}"))
+        .collect::<Vec<_>>()
+        .join(" ")
+    {
+        formatted if formatted.is_empty() => "0m".to_string(),
+        formatted => formatted,
+    }
+}
+
+/// The current time in the local timezone, falling back to UTC when the offset can't be determined.
+pub fn local_now() -> OffsetDateTime {
+    OffsetDateTime::now_local().unwrap_or_else(|_| OffsetDateTime::now_utc())
+}
+
+/// The Monday to Sunday range containing `date`.
+pub fn week_of(date: Date) -> (Date, Date) {
+    let monday = date - Duration::days(date.weekday().number_days_from_monday().into());
+    (monday, monday + Duration::days(6))
+}
+
+/// Works out when a worklog started from a date (`2024-01-17`, `today`, `yesterday` or a weekday
+/// such as `mon`) and a local start time (`9`, `09:15`). Returns `None` when both are empty,
+/// meaning "now".
+pub fn parse_started(date: &str, start: &str) -> Result<Option<OffsetDateTime>, Error> {
+    let now = local_now();
+    Ok(started_from(date, start, now)?.map(|started| with_local_offset(started, now)))
+}
+
+/// Works out how long a worklog is and when it started. `time` is either a duration, in which case
+/// `date` and `start` are used as in [`parse_started`], or a range such as `09:15-10:45`, `9-11:30`
+/// or `14:00-now` that sets the start time itself.
+pub fn parse_time(
+    time: &str,
+    date: &str,
+    start: &str,
+    workday: usize,
+) -> Result<(usize, Option<OffsetDateTime>), Error> {
+    let now = local_now();
+    let (seconds, started) = time_from(time, date, start, workday, now)?;
+    Ok((
+        seconds,
+        started.map(|started| with_local_offset(started, now)),
+    ))
+}
+
+fn with_local_offset(started: PrimitiveDateTime, now: OffsetDateTime) -> OffsetDateTime {
+    let offset =
+        UtcOffset::local_offset_at(started.assume_offset(now.offset())).unwrap_or(now.offset());
+    started.assume_offset(offset)
+}
+
+fn time_from(
+    time: &str,
+    date: &str,
+    start: &str,
+    workday: usize,
+    now: OffsetDateTime,
+) -> Result<(usize, Option<PrimitiveDateTime>), Error> {
+    match time_range(time, now.time())? {
+        Some((seconds, range_start)) => {
+            if !start.trim().is_empty() {
+                return Err(Error::new(
+                    "Give either a time range or a start time, not both".to_string(),
+                ));
+            }
+
+            let date = parse_date(date.trim(), now.date())?;
+            Ok((seconds, Some(PrimitiveDateTime::new(date, range_start))))
         }
+        None => Ok((
+            parse_duration(time, workday)?,
+            started_from(date, start, now)?,
+        )),
+    }
+}
+
+// `09:15-10:45`, `9-11:30` or `14:00-now` as a duration and a start time, or `None` when `time`
+// isn't a range at all
+fn time_range(time: &str, now: Time) -> Result<Option<(usize, Time)>, Error> {
+    let Some((from, to)) = time.split_once('-') else {
+        return Ok(None);
+    };
+
+    // Spans are in characters, relative to the whole of `time`
+    let span = |part: &str, offset: usize| {
+        let leading = part.chars().take_while(|c| c.is_whitespace()).count();
+        let start = offset + leading;
+        start..start + part.trim().chars().count().max(1)
+    };
+    let from_span = span(from, 0);
+    let to_span = span(to, from.chars().count() + 1);
+
+    let start = parse_clock(from.trim()).map_err(|err| Error::at(err.message, from_span))?;
+    let end = if to.trim().eq_ignore_ascii_case("now") {
+        now
+    } else {
+        parse_clock(to.trim()).map_err(|err| Error::at(err.message, to_span.clone()))?
+    };
+
+    if end <= start {
+        return Err(Error::at(
+            format!("The range ends at {} before it starts", to.trim()),
+            to_span,
+        ));
     }
 
-    Ok(seconds)
+    Ok(Some(((end - start).whole_seconds() as usize, start)))
 }
 
-fn parse_match(string: Option<Match>) -> f64 {
-    string
-        .and_then(|value| value.as_str().parse().ok())
-        .unwrap_or_default()
+fn started_from(
+    date: &str,
+    start: &str,
+    now: OffsetDateTime,
+) -> Result<Option<PrimitiveDateTime>, Error> {
+    let (date, start) = (date.trim(), start.trim());
+    if date.is_empty() && start.is_empty() {
+        return Ok(None);
+    }
+
+    let date = parse_date(date, now.date())?;
+    let start = if start.is_empty() {
+        now.time()
+    } else {
+        parse_clock(start)?
+    };
+
+    Ok(Some(PrimitiveDateTime::new(date, start)))
+}
+
+fn parse_date(date: &str, today: Date) -> Result<Date, Error> {
+    let weekday = match date.to_lowercase().as_str() {
+        "" | "today" => return Ok(today),
+        "yesterday" => return Ok(today - Duration::days(1)),
+        "mon" | "monday" => Weekday::Monday,
+        "tue" | "tuesday" => Weekday::Tuesday,
+        "wed" | "wednesday" => Weekday::Wednesday,
+        "thu" | "thursday" => Weekday::Thursday,
+        "fri" | "friday" => Weekday::Friday,
+        "sat" | "saturday" => Weekday::Saturday,
+        "sun" | "sunday" => Weekday::Sunday,
+        _ => {
+            return Date::parse(date, format_description!("[year]-[month]-[day]"))
+                .map_err(|_| Error::new(format!("Date could not be parsed from string `{date}`")))
+        }
+    };
+
+    // The most recent day with that name, which may be today
+    let days_ago =
+        (today.weekday().number_days_from_monday() + 7 - weekday.number_days_from_monday()) % 7;
+    Ok(today - Duration::days(days_ago.into()))
+}
+
+fn parse_clock(clock: &str) -> Result<Time, Error> {
+    let invalid = || {
+        Error::new(format!(
+            "Time of day could not be parsed from string `{clock}`"
+        ))
+    };
+
+    let (hour, minute) = clock.split_once(':').unwrap_or((clock, "0"));
+    let hour = hour.parse().map_err(|_| invalid())?;
+    let minute = minute.parse().map_err(|_| invalid())?;
+
+    Time::from_hms(hour, minute, 0).map_err(|_| invalid())
 }
 
 #[cfg(test)]
 mod test {
-    use super::string_to_seconds;
+    use super::{
+        parse_duration, seconds_to_string, started_from, string_to_seconds, time_from, week_of,
+    };
+    use time::macros::{date, datetime};
 
     #[test]
     fn explicit_hour_with_implicit_minute() {
@@ -90,4 +539,220 @@ mod test {
         let seconds = string_to_seconds("Look alive, sunshine");
         assert!(seconds.is_err())
     }
+
+    #[test]
+    fn rejects_trailing_garbage() {
+        let err = string_to_seconds("Look 5 alive").err().unwrap();
+        assert_eq!(err.span(), Some(0..4));
+
+        let err = string_to_seconds("5 alive").err().unwrap();
+        assert_eq!(err.msg(), "Unknown unit `alive`");
+        assert_eq!(err.span(), Some(2..7));
+        assert_eq!(
+            err.describe("5 alive"),
+            "Unknown unit `alive`\n  5 alive\n    ^^^^^"
+        );
+
+        assert!(string_to_seconds("1h30m!").is_err());
+    }
+
+    #[test]
+    fn spaces_between_components() {
+        assert_eq!(string_to_seconds("1h 30m").ok(), Some(5400));
+        assert_eq!(string_to_seconds(" 1 h 30 ").ok(), Some(5400));
+    }
+
+    #[test]
+    fn hours_and_minutes_with_colon() {
+        assert_eq!(string_to_seconds("1:30").ok(), Some(5400));
+        assert_eq!(string_to_seconds("0:05").ok(), Some(300));
+        assert_eq!(string_to_seconds("1:75").err().unwrap().span(), Some(2..4));
+        assert!(string_to_seconds("1:3").is_err());
+        assert!(string_to_seconds("1:30 extra").is_err());
+        assert_eq!(
+            string_to_seconds("99999999999999999:00")
+                .err()
+                .unwrap()
+                .span(),
+            Some(0..17)
+        );
+    }
+
+    #[test]
+    fn rejects_durations_too_long_to_count() {
+        let err = string_to_seconds("99999999999999999999h").err().unwrap();
+        assert_eq!(err.span(), Some(0..21));
+        assert_eq!(
+            string_to_seconds("1h 99999999999999999999m")
+                .err()
+                .unwrap()
+                .span(),
+            Some(3..24)
+        );
+        assert!(string_to_seconds(&format!("{}h", "9".repeat(400))).is_err());
+    }
+
+    #[test]
+    fn long_unit_words_and_comma_decimals() {
+        assert_eq!(string_to_seconds("90 minutes").ok(), Some(5400));
+        assert_eq!(string_to_seconds("2 hours 15 mins").ok(), Some(8100));
+        assert_eq!(string_to_seconds("1,5h").ok(), Some(5400));
+    }
+
+    #[test]
+    fn days_and_weeks_use_the_workday() {
+        assert_eq!(string_to_seconds("2d").ok(), Some(2 * 8 * 3600));
+        assert_eq!(parse_duration("1d 2h", 7 * 3600).ok(), Some(9 * 3600));
+        assert_eq!(parse_duration("1w", 6 * 3600).ok(), Some(30 * 3600));
+    }
+
+    #[test]
+    fn rejects_out_of_order_and_empty() {
+        let err = string_to_seconds("30m 1h").err().unwrap();
+        assert_eq!(err.span(), Some(4..6));
+        assert!(string_to_seconds("1h 2h").is_err());
+        assert!(string_to_seconds("30m 15").is_err());
+        assert!(string_to_seconds("").is_err());
+        assert!(string_to_seconds("0m").is_err());
+    }
+
+    #[test]
+    fn formats_durations() {
+        assert_eq!(seconds_to_string(5400), "1h 30m");
+        assert_eq!(seconds_to_string(900), "15m");
+        assert_eq!(seconds_to_string(7200), "2h");
+        assert_eq!(seconds_to_string(5399), "1h 29m 59s");
+        assert_eq!(seconds_to_string(20), "20s");
+        assert_eq!(seconds_to_string(3605), "1h 5s");
+        assert_eq!(string_to_seconds(&seconds_to_string(8100)).ok(), Some(8100));
+    }
+
+    #[test]
+    fn formatted_durations_read_back_the_same() {
+        // Every second of the first two days, then steps through much longer ones
+        let samples = (1..=2 * 86_400).chain((1..=1000).map(|step| step * 7_919));
+        for seconds in samples {
+            let formatted = seconds_to_string(seconds);
+            assert_eq!(
+                string_to_seconds(&formatted).ok(),
+                Some(seconds),
+                "{formatted}"
+            );
+        }
+    }
+
+    #[test]
+    fn seconds_have_to_be_written_out() {
+        assert_eq!(string_to_seconds("1m 30s").ok(), Some(90));
+        assert_eq!(string_to_seconds("45 seconds").ok(), Some(45));
+        assert!(string_to_seconds("1h 30m 15").is_err());
+    }
+
+    #[test]
+    fn week_starts_on_monday() {
+        assert_eq!(
+            week_of(date!(2024 - 01 - 17)),
+            (date!(2024 - 01 - 15), date!(2024 - 01 - 21))
+        );
+        assert_eq!(
+            week_of(date!(2024 - 01 - 21)),
+            (date!(2024 - 01 - 15), date!(2024 - 01 - 21))
+        );
+    }
+
+    #[test]
+    fn started_defaults_to_now() {
+        let now = datetime!(2024-01-17 15:30 +1);
+        assert_eq!(started_from("", "", now).ok().unwrap(), None);
+        assert_eq!(
+            started_from("today", "", now).ok().unwrap(),
+            Some(datetime!(2024-01-17 15:30))
+        );
+    }
+
+    #[test]
+    fn started_with_date_and_time() {
+        let now = datetime!(2024-01-17 15:30 +1);
+        assert_eq!(
+            started_from("2024-01-12", "9:15", now).ok().unwrap(),
+            Some(datetime!(2024-01-12 09:15))
+        );
+        assert_eq!(
+            started_from("yesterday", "14", now).ok().unwrap(),
+            Some(datetime!(2024-01-16 14:00))
+        );
+    }
+
+    #[test]
+    fn started_on_weekday() {
+        let now = datetime!(2024-01-17 15:30 +1); // Wednesday
+        assert_eq!(
+            started_from("mon", "09:00", now).ok().unwrap(),
+            Some(datetime!(2024-01-15 09:00))
+        );
+        assert_eq!(
+            started_from("Wednesday", "09:00", now).ok().unwrap(),
+            Some(datetime!(2024-01-17 09:00))
+        );
+        assert_eq!(
+            started_from("thu", "09:00", now).ok().unwrap(),
+            Some(datetime!(2024-01-11 09:00))
+        );
+    }
+
+    #[test]
+    fn started_rejects_nonsense() {
+        let now = datetime!(2024-01-17 15:30 +1);
+        assert!(started_from("someday", "", now).is_err());
+        assert!(started_from("", "25:00", now).is_err());
+        assert!(started_from("", "9:60", now).is_err());
+    }
+
+    #[test]
+    fn ranges_set_duration_and_start() {
+        let now = datetime!(2024-01-17 15:30 +1);
+        let range = |time| time_from(time, "", "", 8 * 3600, now);
+
+        assert_eq!(
+            range("09:15-10:45").ok(),
+            Some((5400, Some(datetime!(2024-01-17 09:15))))
+        );
+        assert_eq!(
+            range("9 - 11:30").ok(),
+            Some((9000, Some(datetime!(2024-01-17 09:00))))
+        );
+        assert_eq!(
+            range("14:00-now").ok(),
+            Some((5400, Some(datetime!(2024-01-17 14:00))))
+        );
+        assert_eq!(
+            time_from("10-10:30", "yesterday", "", 8 * 3600, now).ok(),
+            Some((1800, Some(datetime!(2024-01-16 10:00))))
+        );
+    }
+
+    #[test]
+    fn ranges_point_at_the_bad_end() {
+        let now = datetime!(2024-01-17 15:30 +1);
+        let range = |time| time_from(time, "", "", 8 * 3600, now);
+
+        assert_eq!(range("10:00-9:00").err().unwrap().span(), Some(6..10));
+        assert_eq!(range("10:00-soon").err().unwrap().span(), Some(6..10));
+        assert_eq!(range("25:00-26:00").err().unwrap().span(), Some(0..5));
+        assert!(range("16:00-now").is_err());
+        assert!(time_from("9-10", "", "9:00", 8 * 3600, now).is_err());
+    }
+
+    #[test]
+    fn durations_still_use_the_start_time() {
+        let now = datetime!(2024-01-17 15:30 +1);
+        assert_eq!(
+            time_from("1h30", "", "", 8 * 3600, now).ok(),
+            Some((5400, None))
+        );
+        assert_eq!(
+            time_from("2d", "", "9", 7 * 3600, now).ok(),
+            Some((14 * 3600, Some(datetime!(2024-01-17 09:00))))
+        );
+    }
 }
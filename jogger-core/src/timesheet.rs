@@ -0,0 +1,121 @@
+use std::collections::BTreeMap;
+use time::{Date, Duration};
+
+use crate::jira::Worklog;
+use crate::journal::{JournalEntry, Outcome};
+use crate::time::week_of;
+
+/// Seconds logged per ticket on each day of a week, Monday first.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Timesheet {
+    pub monday: Date,
+    pub rows: BTreeMap<String, [usize; 7]>,
+}
+
+impl Timesheet {
+    /// An empty timesheet for the week containing `date`.
+    pub fn new(date: Date) -> Self {
+        Timesheet {
+            monday: week_of(date).0,
+            rows: BTreeMap::new(),
+        }
+    }
+
+    pub fn from_worklogs(date: Date, worklogs: &[Worklog]) -> Self {
+        let mut timesheet = Timesheet::new(date);
+        for worklog in worklogs {
+            timesheet.add(&worklog.ticket, worklog.started.date(), worklog.seconds);
+        }
+        timesheet
+    }
+
+    /// Builds the week from the local journal, counting only worklogs Jira accepted, as they
+    /// were last changed and leaving out those deleted since.
+    pub fn from_journal(date: Date, entries: &[JournalEntry]) -> Self {
+        let mut accepted: Vec<&JournalEntry> = Vec::new();
+        for entry in entries {
+            let same_worklog = |other: &JournalEntry| {
+                entry.worklog_id.is_some() && other.worklog_id == entry.worklog_id
+            };
+
+            match entry.outcome {
+                Outcome::Submitted => accepted.push(entry),
+                Outcome::Updated => match accepted.iter_mut().find(|other| same_worklog(other)) {
+                    Some(submitted) => *submitted = entry,
+                    None => accepted.push(entry),
+                },
+                Outcome::Deleted => accepted.retain(|other| !same_worklog(other)),
+                Outcome::Queued | Outcome::Failed => {}
+            }
+        }
+
+        let mut timesheet = Timesheet::new(date);
+        for entry in accepted {
+            timesheet.add(&entry.ticket, entry.started().date(), entry.seconds);
+        }
+        timesheet
+    }
+
+    /// Adds `seconds` to `ticket` on `date`, ignoring dates outside the week.
+    pub fn add(&mut self, ticket: &str, date: Date, seconds: usize) {
+        let day = (date - self.monday).whole_days();
+        if (0..7).contains(&day) {
+            self.rows.entry(ticket.to_string()).or_default()[day as usize] += seconds;
+        }
+    }
+
+    pub fn days(&self) -> [Date; 7] {
+        std::array::from_fn(|day| self.monday + Duration::days(day as i64))
+    }
+
+    pub fn day_totals(&self) -> [usize; 7] {
+        std::array::from_fn(|day| self.rows.values().map(|row| row[day]).sum())
+    }
+
+    pub fn total(&self) -> usize {
+        self.day_totals().iter().sum()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::Timesheet;
+    use crate::jira::Worklog;
+    use crate::journal::{Frontend, JournalEntry, Outcome};
+    use time::macros::{date, datetime};
+
+    #[test]
+    fn totals_by_ticket_and_day() {
+        let mut timesheet = Timesheet::new(date!(2024 - 01 - 17));
+        timesheet.add("PIM-1", date!(2024 - 01 - 15), 3600);
+        timesheet.add("PIM-1", date!(2024 - 01 - 15), 1800);
+        timesheet.add("PIM-2", date!(2024 - 01 - 19), 900);
+        timesheet.add("PIM-2", date!(2024 - 01 - 22), 900); // next week
+
+        assert_eq!(timesheet.monday, date!(2024 - 01 - 15));
+        assert_eq!(timesheet.rows["PIM-1"], [5400, 0, 0, 0, 0, 0, 0]);
+        assert_eq!(timesheet.rows["PIM-2"], [0, 0, 0, 0, 900, 0, 0]);
+        assert_eq!(timesheet.day_totals(), [5400, 0, 0, 0, 900, 0, 0]);
+        assert_eq!(timesheet.total(), 6300);
+    }
+
+    #[test]
+    fn journal_edits_replace_and_remove_worklogs() {
+        let worklog = |id: &str, seconds| Worklog {
+            id: id.to_string(),
+            ticket: "PIM-1".to_string(),
+            started: datetime!(2024-01-16 12:00 UTC),
+            seconds,
+            comment: String::new(),
+        };
+        let entries = [
+            JournalEntry::edited(Frontend::Tui, &worklog("1", 3600), Outcome::Submitted),
+            JournalEntry::edited(Frontend::Tui, &worklog("2", 1800), Outcome::Submitted),
+            JournalEntry::edited(Frontend::Tui, &worklog("1", 900), Outcome::Updated),
+            JournalEntry::edited(Frontend::Tui, &worklog("2", 1800), Outcome::Deleted),
+        ];
+
+        let timesheet = Timesheet::from_journal(date!(2024 - 01 - 16), &entries);
+        assert_eq!(timesheet.rows["PIM-1"], [0, 900, 0, 0, 0, 0, 0]);
+    }
+}
@@ -0,0 +1,95 @@
+use std::path::PathBuf;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::time::{Duration, SystemTime};
+
+use crate::catalogs;
+use crate::config::{org_config_path, Error as ConfigError};
+use crate::paths::config_file;
+use crate::preferences::{LoadOptions, Preferences};
+
+const POLL_INTERVAL: Duration = Duration::from_secs(2);
+
+/// Keeps watching the settings files until dropped.
+pub struct ConfigWatcher {
+    stop: Arc<AtomicBool>,
+}
+
+impl Drop for ConfigWatcher {
+    fn drop(&mut self) {
+        self.stop.store(true, Ordering::Relaxed);
+    }
+}
+
+/// Calls `on_change` from a background thread with the settings loaded again, or why they
+/// couldn't be, whenever the user's config, the organisation defaults or the shared catalogs
+/// change. A file is only read once it has stopped changing, so a half saved edit isn't picked
+/// up, and changes made by this process are reported like any other.
+pub fn watch_config(
+    options: LoadOptions,
+    mut on_change: impl FnMut(Result<Preferences, ConfigError>) + Send + 'static,
+) -> ConfigWatcher {
+    let stop = Arc::new(AtomicBool::new(false));
+    let stopped = Arc::clone(&stop);
+
+    std::thread::spawn(move || {
+        let files = watched_files(&options);
+        let mut last = fingerprint(&files);
+        let mut changed = false;
+
+        while !stopped.load(Ordering::Relaxed) {
+            std::thread::sleep(POLL_INTERVAL);
+
+            let current = fingerprint(&files);
+            if current != last {
+                last = current;
+                changed = true;
+            } else if changed && !stopped.load(Ordering::Relaxed) {
+                changed = false;
+                on_change(Preferences::load_with(&options));
+            }
+        }
+    });
+
+    ConfigWatcher { stop }
+}
+
+fn watched_files(options: &LoadOptions) -> Vec<PathBuf> {
+    let mut files = vec![org_config_path()];
+    files.extend(config_file(options.config.as_deref()));
+    files.extend(catalogs::cache_path());
+    files
+}
+
+// When each file was last changed and how big it is, or `None` if it doesn't exist
+fn fingerprint(files: &[PathBuf]) -> Vec<Option<(SystemTime, u64)>> {
+    files
+        .iter()
+        .map(|file| {
+            let metadata = std::fs::metadata(file).ok()?;
+            Some((metadata.modified().ok()?, metadata.len()))
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod test {
+    use super::fingerprint;
+
+    #[test]
+    fn fingerprints_change_with_the_files() {
+        let dir = std::env::temp_dir().join(format!("jogger-watch-{}", std::process::id()));
+        let files = [dir.join("jogger.conf"), dir.join("missing.conf")];
+        std::fs::create_dir_all(&dir).unwrap();
+
+        std::fs::write(&files[0], "jira_url = \"a\"").unwrap();
+        let before = fingerprint(&files);
+        std::fs::write(&files[0], "jira_url = \"ab\"").unwrap();
+        let after = fingerprint(&files);
+
+        assert!(before[0].is_some() && before[1].is_none());
+        assert_ne!(before, after);
+        assert_eq!(after, fingerprint(&files));
+        std::fs::remove_dir_all(dir).ok();
+    }
+}
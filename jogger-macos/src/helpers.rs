@@ -54,6 +54,29 @@ pub fn show_alert(title: &str, message: &str) {
     }
 }
 
+/// Asks the user to go ahead with something, returning whether they pressed `confirm`.
+pub fn show_confirm_alert(title: &str, message: &str, confirm: &str) -> bool {
+    activate_app();
+
+    unsafe {
+        let _pool = NSAutoreleasePool::new(nil);
+
+        let alert: id = msg_send![Class::get("NSAlert").unwrap(), alloc];
+        let alert: id = msg_send![alert, init];
+        let _: () = msg_send![alert, setAlertStyle: 1];
+
+        let title_ns = NSString::alloc(nil).init_str(title);
+        let message_ns = NSString::alloc(nil).init_str(message);
+        let _: () = msg_send![alert, setMessageText: title_ns];
+        let _: () = msg_send![alert, setInformativeText: message_ns];
+        let _: () = msg_send![alert, addButtonWithTitle: NSString::alloc(nil).init_str(confirm)];
+        let _: () = msg_send![alert, addButtonWithTitle: NSString::alloc(nil).init_str("Cancel")];
+
+        let response: isize = msg_send![alert, runModal];
+        response == 1000
+    }
+}
+
 pub fn show_single_input_alert(title: &str, label: &str, placeholder: &str) -> Option<String> {
     show_multi_input_alert(title, &[(label, placeholder)]).and_then(|v| v.into_iter().next())
 }
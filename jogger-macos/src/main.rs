@@ -6,14 +6,20 @@ mod icon;
 use cocoa::appkit::NSTextField;
 use cocoa::base::{id, nil};
 use cocoa::foundation::{NSAutoreleasePool, NSPoint, NSRect, NSSize, NSString};
-use helpers::{activate_app, show_alert, show_alert_on_main_thread, show_multi_input_alert, show_single_input_alert};
+use dispatch::Queue;
+use helpers::{
+    activate_app, show_alert, show_alert_on_main_thread, show_confirm_alert, show_multi_input_alert,
+    show_single_input_alert,
+};
 use icon::create_template_icon;
-use jogger_core::{submit_timelog, time::string_to_seconds, Preferences, TimeLog};
+use jogger_core::{
+    replay_queue, seconds_to_string, submit_or_queue, watch_config, AuthMode, Frontend,
+    JiraError, JiraSession, LoadOptions, Preferences, Rounded, Rounding, Secret, Submission,
+    TimeLog,
+};
 use objc::runtime::Class;
 use objc::{msg_send, sel, sel_impl};
-use std::cell::RefCell;
-use std::rc::Rc;
-use std::sync::{Arc, Mutex};
+use std::sync::{Arc, Mutex, OnceLock};
 use std::thread;
 use std::time::Duration;
 use time::OffsetDateTime;
@@ -45,16 +51,16 @@ fn show_reminder_dialog(prefs: Arc<Mutex<Preferences>>) {
     }
 
     let elapsed = prefs_lock.get_elapsed_seconds();
-    let minutes = elapsed / 60;
+    let elapsed_text = seconds_to_string(elapsed as usize);
 
     let message = if prefs_lock.timer_state.accumulated_seconds > 0 {
         format!(
-            "⏰ Time to log!\n\n{} minutes elapsed\n(+{} minutes accumulated)",
-            minutes,
-            prefs_lock.timer_state.accumulated_seconds / 60
+            "⏰ Time to log!\n\n{} elapsed\n(+{} accumulated)",
+            elapsed_text,
+            seconds_to_string(prefs_lock.timer_state.accumulated_seconds as usize)
         )
     } else {
-        format!("⏰ Time to log!\n\n{} minutes elapsed", minutes)
+        format!("⏰ Time to log!\n\n{} elapsed", elapsed_text)
     };
 
     drop(prefs_lock);
@@ -82,31 +88,25 @@ fn show_reminder_dialog(prefs: Arc<Mutex<Preferences>>) {
                 let _ = alert;
                 if let Some(values) = show_multi_input_alert(
                     "Log Time to Ticket",
-                    &[("Ticket:", "PROJ-123"), ("Time:", &format!("{}m", minutes))],
+                    &[("Ticket:", "PROJ-123"), ("Time:", &elapsed_text)],
                 ) {
                     if values.len() == 2 {
-                        let prefs_lock = prefs.lock().unwrap();
-                        let prefs_ref = Rc::new(RefCell::new(prefs_lock.clone()));
+                        let Some(seconds) = reminder_duration(&prefs, &values[1], elapsed as usize) else {
+                            return;
+                        };
+                        if !confirm_rounding(&prefs, &values[0], seconds) {
+                            return;
+                        }
+                        let session = session();
                         let timelog = TimeLog {
                             ticket_number: values[0].clone(),
-                            time_spent_seconds: string_to_seconds(&values[1])
-                                .unwrap_or(elapsed as usize),
+                            time_spent_seconds: seconds,
                             comment: String::new(),
-                            prefs: prefs_ref,
+                            started: None,
+                            frontend: Frontend::MacOs,
                         };
-                        drop(prefs_lock);
-
-                        match submit_timelog(&timelog) {
-                            Ok(_) => {
-                                let mut prefs_lock = prefs.lock().unwrap();
-                                prefs_lock.update_timer_state(&values[0]);
-                                let _ = prefs_lock.save();
-                                show_alert("Success! ✅", "Time logged successfully!");
-                            }
-                            Err(e) => {
-                                show_alert("Error ❌", &format!("Failed to log time:\n{}", e.msg()))
-                            }
-                        }
+
+                        report_submission(Arc::clone(&prefs), &values[0], seconds, submit_or_queue(&session, &timelog));
                     }
                 }
             }
@@ -117,58 +117,50 @@ fn show_reminder_dialog(prefs: Arc<Mutex<Preferences>>) {
                     if let Some(time_str) = show_single_input_alert(
                         "Log Personal Distraction",
                         "Time:",
-                        &format!("{}m", minutes),
+                        &elapsed_text,
                     ) {
-                        let prefs_lock = prefs.lock().unwrap();
-                        let prefs_ref = Rc::new(RefCell::new(prefs_lock.clone()));
+                        let Some(seconds) = reminder_duration(&prefs, &time_str, elapsed as usize) else {
+                            return;
+                        };
+                        if !confirm_rounding(&prefs, &ticket, seconds) {
+                            return;
+                        }
+                        let session = session();
                         let timelog = TimeLog {
                             ticket_number: ticket.clone(),
-                            time_spent_seconds: string_to_seconds(&time_str)
-                                .unwrap_or(elapsed as usize),
+                            time_spent_seconds: seconds,
                             comment: String::new(),
-                            prefs: prefs_ref,
+                            started: None,
+                            frontend: Frontend::MacOs,
                         };
-                        drop(prefs_lock);
-
-                        match submit_timelog(&timelog) {
-                            Ok(_) => {
-                                let mut prefs_lock = prefs.lock().unwrap();
-                                prefs_lock.update_timer_state(&ticket);
-                                let _ = prefs_lock.save();
-                                show_alert("Success! ✅", "Time logged successfully!");
-                            }
-                            Err(e) => {
-                                show_alert("Error ❌", &format!("Failed to log time:\n{}", e.msg()))
-                            }
-                        }
+
+                        report_submission(Arc::clone(&prefs), &ticket, seconds, submit_or_queue(&session, &timelog));
                     }
                 }
             }
             1002 => {
                 // Continue
                 let _ = alert;
-                let prefs_lock = prefs.lock().unwrap();
-                if let Some(last_ticket) = prefs_lock.timer_state.last_ticket.clone() {
-                    let prefs_ref = Rc::new(RefCell::new(prefs_lock.clone()));
+                let last_ticket = prefs.lock().unwrap().timer_state.last_ticket.clone();
+                if let Some(last_ticket) = last_ticket {
+                    let session = session();
                     let timelog = TimeLog {
                         ticket_number: last_ticket.clone(),
                         time_spent_seconds: elapsed as usize,
                         comment: String::new(),
-                        prefs: prefs_ref,
+                        started: None,
+                        frontend: Frontend::MacOs,
                     };
-                    drop(prefs_lock);
-
-                    match submit_timelog(&timelog) {
-                        Ok(_) => {
-                            let mut prefs_lock = prefs.lock().unwrap();
-                            prefs_lock.update_timer_state(&last_ticket);
-                            let _ = prefs_lock.save();
-                            show_alert("Success! ✅", "Time logged successfully!");
-                        }
-                        Err(e) => {
-                            show_alert("Error ❌", &format!("Failed to log time:\n{:?}", e.msg()))
-                        }
+                    if !confirm_rounding(&prefs, &last_ticket, elapsed as usize) {
+                        return;
                     }
+
+                    report_submission(
+                        Arc::clone(&prefs),
+                        &last_ticket,
+                        elapsed as usize,
+                        submit_or_queue(&session, &timelog),
+                    );
                 } else {
                     show_alert("Error ❌", "No previous ticket to continue with!");
                 }
@@ -186,8 +178,114 @@ fn show_reminder_dialog(prefs: Arc<Mutex<Preferences>>) {
     }
 }
 
+// Talks to Jira until the settings change, set up once they are loaded
+static SESSION: OnceLock<Mutex<JiraSession>> = OnceLock::new();
+
+fn session() -> JiraSession {
+    SESSION
+        .get()
+        .expect("the session is set up at launch")
+        .lock()
+        .unwrap()
+        .clone()
+}
+
+// Makes the session again from `prefs`, keeping its HTTP client
+fn renew_session(prefs: &Preferences) {
+    if let Some(session) = SESSION.get() {
+        let mut session = session.lock().unwrap();
+        *session = session.with_preferences(prefs.clone());
+    }
+}
+
+// The time typed into a reminder, or the elapsed time when it was left empty. Explains what's
+// wrong and returns `None` when it can't be read, rather than quietly logging something else.
+fn reminder_duration(prefs: &Arc<Mutex<Preferences>>, typed: &str, elapsed: usize) -> Option<usize> {
+    if typed.trim().is_empty() {
+        return Some(elapsed);
+    }
+
+    let parsed = prefs.lock().unwrap().parse_duration(typed);
+    match parsed {
+        Ok(seconds) => Some(seconds),
+        Err(e) => {
+            show_alert(
+                "Invalid Time ⚠️",
+                &format!("Could not parse time:\n{}", e.describe(typed)),
+            );
+            None
+        }
+    }
+}
+
+// When the rounding policy would log something other than what was entered, check the user is
+// happy with that first
+fn confirm_rounding(prefs: &Arc<Mutex<Preferences>>, ticket: &str, seconds: usize) -> bool {
+    let rounded = prefs.lock().unwrap().round(ticket, seconds);
+    rounded.raw == rounded.rounded
+        || show_confirm_alert(
+            "Rounded Time ⏱️",
+            &format!("{} will be logged to {}.", rounded, ticket),
+            "Log",
+        )
+}
+
 // Helper for single input
 
+// Record the outcome of a submission and let the user know how it went
+fn report_submission(
+    prefs: Arc<Mutex<Preferences>>,
+    ticket: &str,
+    raw: usize,
+    result: Result<Submission, JiraError>,
+) {
+    match result {
+        Ok(Submission::Submitted(worklog)) => {
+            let mut prefs_lock = prefs.lock().unwrap();
+            prefs_lock.record_worklog(&worklog);
+            let _ = prefs_lock.save();
+            let destination = prefs_lock.describe_ticket(ticket);
+            drop(prefs_lock);
+            show_alert(
+                "Success! ✅",
+                &format!(
+                    "{} logged to {}!",
+                    Rounded { raw, rounded: worklog.seconds },
+                    destination
+                ),
+            );
+        }
+        Ok(Submission::Queued(e)) => {
+            let mut prefs_lock = prefs.lock().unwrap();
+            prefs_lock.update_timer_state(ticket);
+            let _ = prefs_lock.save();
+            drop(prefs_lock);
+            show_alert(
+                "Saved for Later 📥",
+                &format!(
+                    "{}\n\nYour time for {} has been queued and will be submitted once Jira can be reached.",
+                    e, ticket
+                ),
+            );
+        }
+        Err(e) => show_submit_error(prefs, &e),
+    }
+}
+
+// Explain a failed submission, sending the user to Preferences when their credentials are wrong
+fn show_submit_error(prefs: Arc<Mutex<Preferences>>, err: &JiraError) {
+    match err {
+        JiraError::Unauthorized => {
+            show_alert(
+                "Not Signed In 🔑",
+                &format!("{}\n\nCheck your username and API token or password.", err),
+            );
+            show_preferences_dialog(prefs);
+        }
+        _ => show_alert("Error ❌", &format!("Failed to log time:\n{}", err)),
+    }
+}
+
 // Helper to show native macOS alert with multiple text inputs
 
 fn show_ticket_dialog(prefs: Arc<Mutex<Preferences>>) {
@@ -214,58 +312,71 @@ fn submit_time_log(
     time_str: String,
     comment: String,
 ) {
-    match jogger_core::string_to_seconds(&time_str) {
+    let parsed = prefs.lock().unwrap().parse_duration(&time_str);
+    match parsed {
         Ok(seconds) => {
-            let prefs_clone = prefs.lock().unwrap().clone();
+            if !confirm_rounding(&prefs, &ticket, seconds) {
+                return;
+            }
+            let session = session();
             let ticket_clone = ticket.clone();
             let prefs_arc = Arc::clone(&prefs);
-            let time_str_clone = time_str.clone();
+            let log = TimeLog {
+                time_spent_seconds: seconds,
+                comment,
+                ticket_number: ticket,
+                started: None,
+                frontend: Frontend::MacOs,
+            };
 
             std::thread::spawn(move || {
-                let prefs_rc = Rc::new(RefCell::new(prefs_clone));
-
-                let log = TimeLog {
-                    time_spent_seconds: seconds,
-                    comment,
-                    ticket_number: ticket_clone.clone(),
-                    prefs: prefs_rc,
-                };
-
-                match submit_timelog(&log) {
-                    Ok(_) => {
-                        let mut prefs_lock = prefs_arc.lock().unwrap();
-                        prefs_lock.update_timer_state(&ticket_clone);
-                        let _ = prefs_lock.save();
-                        
-                        show_alert_on_main_thread(
-                            "Success! ✅".to_string(),
-                            format!("Logged {} to {}", time_str_clone, ticket_clone),
-                        );
-                    }
-                    Err(e) => {
-                        eprintln!("❌ Error: {}", e.msg());
-                        show_alert_on_main_thread(
-                            "Error ❌".to_string(),
-                            format!("Failed to log time:\n{}", e.msg())
-                        );
-                    }
+                let result = submit_or_queue(&session, &log);
+                if let Err(e) = &result {
+                    eprintln!("❌ Error: {}", e);
                 }
+
+                Queue::main()
+                    .exec_async(move || report_submission(prefs_arc, &ticket_clone, seconds, result));
             });
         }
         Err(e) => {
             show_alert(
                 "Invalid Time ⚠️",
-                &format!("Could not parse time:\n{}", e.msg()),
+                &format!("Could not parse time:\n{}", e.describe(&time_str)),
             );
         }
     }
 }
 
+// Submit anything queued while Jira was unreachable. When `quiet`, only speak up if something
+// was actually submitted.
+fn replay_pending(prefs: Arc<Mutex<Preferences>>, quiet: bool) {
+    let session = session();
+
+    std::thread::spawn(move || match replay_queue(&session) {
+        Ok(report) if report.submitted.is_empty() && quiet => {}
+        Ok(report) => show_alert_on_main_thread(
+            "Pending Worklogs 📤".to_string(),
+            format!(
+                "Submitted {} worklog(s), {} still pending",
+                report.submitted.len(),
+                report.remaining
+            ),
+        ),
+        Err(e) => {
+            eprintln!("❌ Error: {}", e);
+            if !quiet {
+                show_alert_on_main_thread("Error ❌".to_string(), format!("{}", e));
+            }
+        }
+    });
+}
+
 // Helper to show project/meeting selector with dropdowns
 fn show_meeting_selector_dropdown(prefs: Arc<Mutex<Preferences>>) -> Option<String> {
     activate_app();
 
-    let projects = prefs.lock().unwrap().custom_meetings.clone();
+    let projects = prefs.lock().unwrap().meetings();
 
     if projects.is_empty() {
         return None;
@@ -339,7 +450,7 @@ fn show_meeting_selector_dropdown(prefs: Arc<Mutex<Preferences>>) -> Option<Stri
         ) pullsDown: false];
 
         for meeting in &selected_project.meetings {
-            let item_title = NSString::alloc(nil).init_str(&format!("{} - {}", meeting.0, meeting.1));
+            let item_title = NSString::alloc(nil).init_str(&format!("{} - {}", meeting.label, meeting.ticket));
             let _: () = msg_send![ticket_popup, addItemWithTitle: item_title];
         }
 
@@ -354,7 +465,7 @@ fn show_meeting_selector_dropdown(prefs: Arc<Mutex<Preferences>>) -> Option<Stri
             let ticket_idx: isize = msg_send![ticket_popup, indexOfSelectedItem];
             if ticket_idx >= 0 {
                 if let Some(meeting) = selected_project.meetings.get(ticket_idx as usize) {
-                    return Some(meeting.1.clone());
+                    return Some(meeting.ticket.clone());
                 }
             }
         }
@@ -364,7 +475,7 @@ fn show_meeting_selector_dropdown(prefs: Arc<Mutex<Preferences>>) -> Option<Stri
 }
 
 fn show_distraction_dialog(prefs: Arc<Mutex<Preferences>>) {
-    let projects = prefs.lock().unwrap().custom_meetings.clone();
+    let projects = prefs.lock().unwrap().meetings();
 
     if projects.is_empty() {
         show_alert(
@@ -392,15 +503,24 @@ fn show_distraction_dialog(prefs: Arc<Mutex<Preferences>>) {
 }
 
 fn show_preferences_dialog(prefs: Arc<Mutex<Preferences>>) {
-    activate_app();
-
     let current = prefs.lock().unwrap().clone();
+    edit_preferences(prefs, current);
+}
+
+// Shows the preferences dialog filled in with `current`, which may hold unsaved edits
+// Where the API token is among the fields of the preferences dialog
+const TOKEN_FIELD: usize = 2;
+
+fn edit_preferences(prefs: Arc<Mutex<Preferences>>, current: Preferences) {
+    activate_app();
 
+    let workday_hours = current.workday_hours.to_string();
     let fields = [
         ("Name:", &current.name as &str),
-        ("Email:", &current.email),
-        ("API Key:", &current.api_key),
+        ("Email or Username:", &current.email),
+        ("API Token or Password:", current.api_key.expose()),
         ("Jira URL:", &current.jira_url),
+        ("Hours per Workday:", &workday_hours),
     ];
 
     unsafe {
@@ -414,7 +534,7 @@ fn show_preferences_dialog(prefs: Arc<Mutex<Preferences>>) {
         let _: () = msg_send![alert, setMessageText: title_ns];
 
         let container: id = msg_send![Class::get("NSView").unwrap(), alloc];
-        let height = (fields.len() as f64) * 50.0 + 120.0; // Extra space for reminder settings
+        let height = (fields.len() as f64) * 50.0 + 230.0; // Extra space for rounding, auth and reminder settings
         let container: id = msg_send![container, initWithFrame: NSRect::new(
             NSPoint::new(0., 0.),
             NSSize::new(400., height)
@@ -438,7 +558,11 @@ fn show_preferences_dialog(prefs: Arc<Mutex<Preferences>>) {
             let _: () = msg_send![label_view, setSelectable: false];
             let _: () = msg_send![container, addSubview: label_view];
 
-            let text_field = NSTextField::alloc(nil);
+            // The token is typed into a field that shows dots instead
+            let text_field = match i == TOKEN_FIELD {
+                true => msg_send![Class::get("NSSecureTextField").unwrap(), alloc],
+                false => NSTextField::alloc(nil),
+            };
             let text_field: id = msg_send![text_field, initWithFrame: NSRect::new(
                 NSPoint::new(0., y),
                 NSSize::new(400., 24.)
@@ -450,7 +574,68 @@ fn show_preferences_dialog(prefs: Arc<Mutex<Preferences>>) {
             text_fields.push(text_field);
         }
 
-        // Reminder settings section - below all text fields
+        // Rounding - below all text fields
+        let rounding_y = 170.0;
+
+        let rounding_label: id = msg_send![Class::get("NSTextField").unwrap(), alloc];
+        let rounding_label: id = msg_send![rounding_label, initWithFrame: NSRect::new(
+            NSPoint::new(0., rounding_y + 25.),
+            NSSize::new(400., 20.)
+        )];
+        let _: () = msg_send![rounding_label, setStringValue: NSString::alloc(nil).init_str("Rounding:")];
+        let _: () = msg_send![rounding_label, setBezeled: false];
+        let _: () = msg_send![rounding_label, setDrawsBackground: false];
+        let _: () = msg_send![rounding_label, setEditable: false];
+        let _: () = msg_send![rounding_label, setSelectable: false];
+        let _: () = msg_send![container, addSubview: rounding_label];
+
+        let rounding_popup: id = msg_send![Class::get("NSPopUpButton").unwrap(), alloc];
+        let rounding_popup: id = msg_send![rounding_popup, initWithFrame: NSRect::new(
+            NSPoint::new(0., rounding_y - 5.),
+            NSSize::new(400., 30.)
+        )];
+        for preset in Rounding::PRESETS {
+            let _: () = msg_send![rounding_popup, addItemWithTitle: NSString::alloc(nil).init_str(&preset.to_string())];
+        }
+        // A custom rounding from the config file has no entry, so leave it alone unless changed
+        let rounding_idx = Rounding::PRESETS
+            .iter()
+            .position(|preset| *preset == current.rounding);
+        let _: () = msg_send![rounding_popup, selectItemAtIndex: rounding_idx.map_or(-1, |idx| idx as isize)];
+        let _: () = msg_send![container, addSubview: rounding_popup];
+
+        // Authentication mode - below the rounding
+        let auth_y = 110.0;
+
+        let auth_label: id = msg_send![Class::get("NSTextField").unwrap(), alloc];
+        let auth_label: id = msg_send![auth_label, initWithFrame: NSRect::new(
+            NSPoint::new(0., auth_y + 25.),
+            NSSize::new(400., 20.)
+        )];
+        let _: () = msg_send![auth_label, setStringValue: NSString::alloc(nil).init_str("Sign In With:")];
+        let _: () = msg_send![auth_label, setBezeled: false];
+        let _: () = msg_send![auth_label, setDrawsBackground: false];
+        let _: () = msg_send![auth_label, setEditable: false];
+        let _: () = msg_send![auth_label, setSelectable: false];
+        let _: () = msg_send![container, addSubview: auth_label];
+
+        let auth_popup: id = msg_send![Class::get("NSPopUpButton").unwrap(), alloc];
+        let auth_popup: id = msg_send![auth_popup, initWithFrame: NSRect::new(
+            NSPoint::new(0., auth_y - 5.),
+            NSSize::new(400., 30.)
+        )];
+        for mode in AuthMode::ALL {
+            let _: () =
+                msg_send![auth_popup, addItemWithTitle: NSString::alloc(nil).init_str(&mode.to_string())];
+        }
+        let auth_idx = AuthMode::ALL
+            .iter()
+            .position(|mode| *mode == current.auth_mode)
+            .unwrap_or_default() as isize;
+        let _: () = msg_send![auth_popup, selectItemAtIndex: auth_idx];
+        let _: () = msg_send![container, addSubview: auth_popup];
+
+        // Reminder settings section - below the auth mode
         let reminder_y = 60.0;
 
         // Checkbox
@@ -489,10 +674,13 @@ fn show_preferences_dialog(prefs: Arc<Mutex<Preferences>>) {
         let _: () = msg_send![alert, setAccessoryView: container];
         let _: () = msg_send![alert, addButtonWithTitle: NSString::alloc(nil).init_str("Save")];
         let _: () = msg_send![alert, addButtonWithTitle: NSString::alloc(nil).init_str("Cancel")];
+        let _: () =
+            msg_send![alert, addButtonWithTitle: NSString::alloc(nil).init_str("Test Connection")];
 
         let response: isize = msg_send![alert, runModal];
 
-        if response == 1000 {
+        // 1000 = Save, 1002 = Test Connection
+        if response == 1000 || response == 1002 {
             let mut new_prefs = current;
 
             for (i, text_field) in text_fields.iter().enumerate() {
@@ -507,8 +695,15 @@ fn show_preferences_dialog(prefs: Arc<Mutex<Preferences>>) {
                     match i {
                         0 => new_prefs.name = string_value,
                         1 => new_prefs.email = string_value,
-                        2 => new_prefs.api_key = string_value,
+                        2 => new_prefs.api_key = Secret::new(string_value),
                         3 => new_prefs.jira_url = string_value,
+                        4 => {
+                            if let Ok(hours) = string_value.trim().parse::<f64>() {
+                                if hours > 0.0 && hours <= 24.0 {
+                                    new_prefs.workday_hours = hours;
+                                }
+                            }
+                        }
                         _ => {}
                     }
                 }
@@ -541,8 +736,42 @@ fn show_preferences_dialog(prefs: Arc<Mutex<Preferences>>) {
                 _ => 60,
             };
 
+            let auth_selected: isize = msg_send![auth_popup, indexOfSelectedItem];
+            new_prefs.auth_mode = AuthMode::ALL
+                .get(auth_selected.max(0) as usize)
+                .copied()
+                .unwrap_or_default();
+
+            let rounding_selected: isize = msg_send![rounding_popup, indexOfSelectedItem];
+            if let Some(rounding) = usize::try_from(rounding_selected)
+                .ok()
+                .and_then(|idx| Rounding::PRESETS.get(idx))
+            {
+                new_prefs.rounding = *rounding;
+            }
+
+            if new_prefs.auth_mode.needs_username() && new_prefs.email.trim().is_empty() {
+                show_alert(
+                    "Missing Username ⚠️",
+                    &format!("{} needs your email or username.", new_prefs.auth_mode),
+                );
+                edit_preferences(prefs, new_prefs);
+                return;
+            }
+
+            if response == 1002 {
+                // Check the unsaved values, then bring the dialog back with them still filled in
+                match session().with_preferences(new_prefs.clone()).test_connection() {
+                    Ok(user) => show_alert("Connected ✅", &format!("Signed in to Jira as {}", user)),
+                    Err(e) => show_alert("Connection Failed ❌", &e.to_string()),
+                }
+                edit_preferences(prefs, new_prefs);
+                return;
+            }
+
             match new_prefs.save() {
                 Ok(_) => {
+                    renew_session(&new_prefs);
                     *prefs.lock().unwrap() = new_prefs;
                     show_alert("Success! ✅", "Preferences saved successfully!");
                 }
@@ -566,7 +795,21 @@ fn main() {
     println!("🏃🏼‍♀️ Jogger - Menu Bar App");
     println!("✨ Look for Gerald the Gentleman Runner in your menu bar!");
 
-    let prefs = Arc::new(Mutex::new(Preferences::load().unwrap_or_default()));
+    // Carrying on with default settings would overwrite the user's file the next time they're saved
+    let prefs = match Preferences::load() {
+        Ok(prefs) => Arc::new(Mutex::new(prefs)),
+        Err(e) => {
+            eprintln!("❌ Error: {}", e);
+            show_alert(
+                "Settings Error ❌",
+                &format!(
+                    "Your settings could not be loaded:\n\n{}\n\nFix the file and start Jogger again.",
+                    e
+                ),
+            );
+            std::process::exit(1);
+        }
+    };
 
     // Initialize timer if this is first run
     {
@@ -577,18 +820,47 @@ fn main() {
         }
     }
 
+    SESSION.set(Mutex::new(prefs.lock().unwrap().session())).ok();
+
+    let key_errors = prefs.lock().unwrap().describe_key_errors();
+    if let Some(message) = key_errors {
+        show_alert("API Token Missing ⚠️", &message);
+    }
+    replay_pending(Arc::clone(&prefs), true);
+
+    // Pick up edits made in the terminal app or by hand without restarting
+    let prefs_reload = Arc::clone(&prefs);
+    let _watcher = watch_config(LoadOptions::default(), move |reloaded| match reloaded {
+        Ok(reloaded) => {
+            if let Some(message) = reloaded.describe_key_errors() {
+                show_alert_on_main_thread("API Token Missing ⚠️".to_string(), message);
+            }
+            renew_session(&reloaded);
+            *prefs_reload.lock().unwrap() = reloaded;
+        }
+        Err(e) => show_alert_on_main_thread(
+            "Settings Error ❌".to_string(),
+            format!(
+                "Your settings could not be reloaded:\n\n{}\n\nThe previous settings are still in use.",
+                e
+            ),
+        ),
+    });
+
     let event_loop: EventLoop<UserEvent> = EventLoop::with_user_event().build().unwrap();
 
     // Create menu
     let menu = Menu::new();
     let log_ticket = MenuItem::new("📝 Log Time to Ticket", true, None);
     let log_distraction = MenuItem::new("☕ Log Personal Distraction", true, None);
+    let retry_pending = MenuItem::new("📤 Retry Pending Worklogs", true, None);
     let preferences = MenuItem::new("⚙️  Preferences", true, None);
     let about_gerald = MenuItem::new("About Gerald...", true, None); // Easter egg!
     let quit = MenuItem::new("Quit", true, None);
 
     menu.append(&log_ticket).unwrap();
     menu.append(&log_distraction).unwrap();
+    menu.append(&retry_pending).unwrap();
     menu.append(&preferences).unwrap();
     menu.append(&about_gerald).unwrap();
     menu.append(&quit).unwrap();
@@ -645,6 +917,8 @@ fn main() {
                 show_ticket_dialog(prefs);
             } else if event.id == log_distraction.id() {
                 show_distraction_dialog(prefs);
+            } else if event.id == retry_pending.id() {
+                replay_pending(prefs, false);
             } else if event.id == preferences.id() {
                 show_preferences_dialog(prefs);
             } else if event.id == about_gerald.id() {
@@ -0,0 +1,603 @@
+use jogger_core::{
+    load_catalog, local_now, parse_override, profile_problems, replay_queue, seconds_to_string,
+    submit_or_queue, week_of, Frontend, JiraError, LoadOptions, PrefRef, Preferences, Submission,
+    TimeLog,
+};
+use std::{cell::RefCell, rc::Rc};
+
+const USAGE: &str = "Usage:
+  jogger                                              Open the interactive interface
+  jogger log <ticket> <time> [options]                Log time to a ticket
+  jogger distraction <project> <meeting> <time> [options]
+                                                      Log time to a configured meeting ticket
+  jogger worklogs [today|week]                        Show the time you have logged
+  jogger replay                                       Submit worklogs queued while Jira was unreachable
+  jogger catalogs [refresh]                           List or refresh shared meeting catalogs
+  jogger catalogs add|remove <source>                 Add or remove a catalog file path or URL
+  jogger config                                       Show each setting and where it came from
+  jogger profiles [ticket]                            List Jira profiles, or show which one a ticket goes to
+  jogger help                                         Show this message
+
+Options:
+  -m, --message <comment>                             Comment to attach to the worklog
+  -d, --date <date>                                   Day the work happened: today, yesterday, a weekday
+                                                      name or YYYY-MM-DD (defaults to today)
+  -s, --start <time>                                  Time the work started, e.g. 9:30 (defaults to now)
+  --set <key>=<value>                                 Override a setting for this run, e.g.
+                                                      --set jira_url=https://jira.company.com
+  --config <path>                                     Use another config file, as does JOGGER_CONFIG
+
+Examples:
+  jogger log PROJ-123 1h30 -m \"fixed import bug\"
+  jogger distraction PIM Billable 15m
+  jogger log PROJ-123 15m --date yesterday --start 9:30
+  jogger log PROJ-123 10:00-11:30
+
+<time> is a duration such as 1h30, 1:30 or 2d, or a range such as 09:15-10:45 or 14:00-now";
+
+#[derive(Debug, PartialEq)]
+enum Command {
+    Log {
+        ticket: String,
+        time: String,
+        comment: String,
+        started: Started,
+    },
+    Distraction {
+        project: String,
+        meeting: String,
+        time: String,
+        comment: String,
+        started: Started,
+    },
+    Worklogs {
+        week: bool,
+    },
+    Replay,
+    Catalogs(CatalogAction),
+    Config,
+    Profiles {
+        ticket: Option<String>,
+    },
+    Help,
+}
+
+#[derive(Debug, PartialEq)]
+enum CatalogAction {
+    List,
+    Refresh,
+    Add(String),
+    Remove(String),
+}
+
+/// The unparsed `--date` and `--start` options.
+#[derive(Debug, Default, PartialEq)]
+struct Started {
+    date: String,
+    start: String,
+}
+
+/// Removes `--set key=value` and `--config <path>` from `args`, returning them as options for
+/// loading settings. These apply to the interface as well as to commands.
+pub fn take_options(args: &mut Vec<String>) -> Result<LoadOptions, String> {
+    let mut options = LoadOptions::default();
+    let mut rest = Vec::new();
+    let mut given = std::mem::take(args).into_iter();
+
+    while let Some(arg) = given.next() {
+        if let Some(setting) = arg.strip_prefix("--set") {
+            match setting {
+                "" => {
+                    let setting = given
+                        .next()
+                        .ok_or_else(|| "`--set` requires a key=value".to_string())?;
+                    options.overrides.push(parse_override(&setting)?);
+                }
+                setting if setting.starts_with('=') => {
+                    options.overrides.push(parse_override(&setting[1..])?)
+                }
+                _ => rest.push(arg),
+            }
+        } else if let Some(path) = arg.strip_prefix("--config") {
+            match path {
+                "" => {
+                    let path = given
+                        .next()
+                        .ok_or_else(|| "`--config` requires a path".to_string())?;
+                    options.config = Some(path.into());
+                }
+                path if path.starts_with('=') => options.config = Some(path[1..].into()),
+                _ => rest.push(arg),
+            }
+        } else {
+            rest.push(arg);
+        }
+    }
+
+    *args = rest;
+    Ok(options)
+}
+
+/// Runs jogger headlessly, returning the process exit code.
+pub fn run(args: &[String], options: &LoadOptions) -> i32 {
+    let command = match parse(args) {
+        Ok(command) => command,
+        Err(err) => {
+            eprintln!("error: {err}\n\n{USAGE}");
+            return 2;
+        }
+    };
+
+    let prefs = match Preferences::load_with(options) {
+        Ok(prefs) => Rc::new(RefCell::new(prefs)),
+        Err(err) => {
+            eprintln!("error: {err}");
+            return 1;
+        }
+    };
+    for err in prefs.borrow().key_errors() {
+        eprintln!("warning: {err}, the API key is left empty");
+    }
+
+    let result = match command {
+        Command::Help => {
+            println!("{USAGE}");
+            Ok(())
+        }
+        Command::Log {
+            ticket,
+            time,
+            comment,
+            started,
+        } => log_time(prefs, ticket, &time, comment, &started),
+        Command::Distraction {
+            project,
+            meeting,
+            time,
+            comment,
+            started,
+        } => {
+            // Let go of `prefs` before logging, which needs to borrow it mutably
+            let ticket = resolve_meeting(&prefs.borrow(), &project, &meeting);
+            ticket.and_then(|ticket| log_time(Rc::clone(&prefs), ticket, &time, comment, &started))
+        }
+        Command::Worklogs { week } => show_worklogs(&prefs.borrow(), week),
+        Command::Replay => replay(&prefs.borrow()),
+        Command::Catalogs(action) => catalogs(&mut prefs.borrow_mut(), action),
+        Command::Config => {
+            show_config(&prefs.borrow());
+            Ok(())
+        }
+        Command::Profiles { ticket } => {
+            show_profiles(&prefs.borrow(), ticket.as_deref());
+            Ok(())
+        }
+    };
+
+    match result {
+        Ok(_) => 0,
+        Err(err) => {
+            eprintln!("error: {err}");
+            1
+        }
+    }
+}
+
+fn parse(args: &[String]) -> Result<Command, String> {
+    let mut positional = Vec::new();
+    let mut comment = String::new();
+    let mut started = Started::default();
+    let mut args = args.iter();
+
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "-m" | "--message" => {
+                comment = args
+                    .next()
+                    .ok_or_else(|| format!("`{arg}` requires a comment"))?
+                    .to_string();
+            }
+            "-d" | "--date" => {
+                started.date = args
+                    .next()
+                    .ok_or_else(|| format!("`{arg}` requires a date"))?
+                    .to_string();
+            }
+            "-s" | "--start" => {
+                started.start = args
+                    .next()
+                    .ok_or_else(|| format!("`{arg}` requires a time"))?
+                    .to_string();
+            }
+            "-h" | "--help" => return Ok(Command::Help),
+            flag if flag.starts_with('-') && flag.len() > 1 => {
+                return Err(format!("unknown option `{flag}`"))
+            }
+            value => positional.push(value.to_string()),
+        }
+    }
+
+    let mut positional = positional.into_iter();
+    let command = positional.next().unwrap_or_default();
+    let rest: Vec<String> = positional.collect();
+
+    match (command.as_str(), rest.as_slice()) {
+        ("help", _) => Ok(Command::Help),
+        ("log", [ticket, time]) => Ok(Command::Log {
+            ticket: ticket.to_string(),
+            time: time.to_string(),
+            comment,
+            started,
+        }),
+        ("log", _) => Err("`log` expects a ticket and a time".to_string()),
+        ("distraction", [project, meeting, time]) => Ok(Command::Distraction {
+            project: project.to_string(),
+            meeting: meeting.to_string(),
+            time: time.to_string(),
+            comment,
+            started,
+        }),
+        ("distraction", _) => {
+            Err("`distraction` expects a project, a meeting type and a time".to_string())
+        }
+        ("worklogs", []) => Ok(Command::Worklogs { week: false }),
+        ("worklogs", [range]) if range == "today" => Ok(Command::Worklogs { week: false }),
+        ("worklogs", [range]) if range == "week" => Ok(Command::Worklogs { week: true }),
+        ("worklogs", _) => Err("`worklogs` expects either `today` or `week`".to_string()),
+        ("replay", []) => Ok(Command::Replay),
+        ("catalogs", []) => Ok(Command::Catalogs(CatalogAction::List)),
+        ("catalogs", [action]) if action == "refresh" => {
+            Ok(Command::Catalogs(CatalogAction::Refresh))
+        }
+        ("catalogs", [action, source]) if action == "add" => {
+            Ok(Command::Catalogs(CatalogAction::Add(source.to_string())))
+        }
+        ("catalogs", [action, source]) if action == "remove" => {
+            Ok(Command::Catalogs(CatalogAction::Remove(source.to_string())))
+        }
+        ("config", []) => Ok(Command::Config),
+        ("profiles", []) => Ok(Command::Profiles { ticket: None }),
+        ("profiles", [ticket]) => Ok(Command::Profiles {
+            ticket: Some(ticket.to_string()),
+        }),
+        ("catalogs", _) => {
+            Err("`catalogs` expects `refresh`, or `add` or `remove` with a source".to_string())
+        }
+        (other, _) => Err(format!("unknown command `{other}`")),
+    }
+}
+
+fn resolve_meeting(prefs: &Preferences, project: &str, meeting: &str) -> Result<String, String> {
+    let projects = prefs.meetings();
+    let project = projects
+        .iter()
+        .find(|p| p.name.eq_ignore_ascii_case(project))
+        .ok_or_else(|| format!("no project named `{project}` is configured"))?;
+
+    project
+        .meeting(meeting)
+        .map(|meeting| meeting.ticket.clone())
+        .ok_or_else(|| {
+            let available = project
+                .meetings
+                .iter()
+                .map(|meeting| meeting.label.clone())
+                .collect::<Vec<_>>()
+                .join(", ");
+            format!(
+                "project `{}` has no `{meeting}` meeting (available: {available})",
+                project.name
+            )
+        })
+}
+
+fn log_time(
+    prefs: PrefRef,
+    ticket: String,
+    time: &str,
+    comment: String,
+    started: &Started,
+) -> Result<(), String> {
+    let (seconds, started) = prefs
+        .borrow()
+        .parse_time(time, &started.date, &started.start)
+        .map_err(|err| err.describe(time))?;
+
+    let session = prefs.borrow().session();
+    let submission = submit_or_queue(
+        &session,
+        &TimeLog {
+            time_spent_seconds: seconds,
+            comment,
+            ticket_number: ticket.clone(),
+            started,
+            frontend: Frontend::Cli,
+        },
+    )
+    .map_err(describe)?;
+
+    let mut prefs = prefs.borrow_mut();
+    let rounded = prefs.round(&ticket, seconds);
+    match submission {
+        Submission::Submitted(worklog) => {
+            prefs.record_worklog(&worklog);
+            println!("Logged {rounded} to {}", prefs.describe_ticket(&ticket));
+        }
+        Submission::Queued(err) => {
+            prefs.update_timer_state(&ticket);
+            println!("{err}\nQueued {rounded} for {ticket}, run `jogger replay` to retry");
+        }
+    }
+    prefs.save().ok();
+
+    Ok(())
+}
+
+fn replay(prefs: &Preferences) -> Result<(), String> {
+    let report = replay_queue(&prefs.session()).map_err(|err| err.to_string())?;
+
+    for worklog in &report.submitted {
+        println!(
+            "Logged {} to {}",
+            seconds_to_string(worklog.seconds),
+            prefs.describe_ticket(&worklog.ticket)
+        );
+    }
+    println!("{} worklog(s) still pending", report.remaining);
+
+    Ok(())
+}
+
+fn catalogs(prefs: &mut Preferences, action: CatalogAction) -> Result<(), String> {
+    match action {
+        CatalogAction::List => {}
+        CatalogAction::Refresh => report_refresh(prefs),
+        CatalogAction::Add(source) => {
+            if prefs.catalog_sources.contains(&source) {
+                return Err(format!("`{source}` is already listed"));
+            }
+            // Check the catalog can be read before keeping it
+            let projects = load_catalog(&source).map_err(|err| err.to_string())?;
+            if let Err(err) = prefs.add_catalog(&source, projects) {
+                eprintln!("warning: {err}");
+            }
+        }
+        CatalogAction::Remove(source) => {
+            if !prefs.catalog_sources.contains(&source) {
+                return Err(format!("`{source}` is not listed"));
+            }
+            if let Err(err) = prefs.remove_catalog(&source) {
+                eprintln!("warning: {err}");
+            }
+        }
+    }
+
+    for source in &prefs.catalog_sources {
+        match prefs.shared_catalogs.iter().find(|c| &c.source == source) {
+            Some(catalog) => {
+                let fetched = catalog.fetched();
+                println!(
+                    "{source}  {} meeting(s), fetched {} {:02}:{:02}",
+                    catalog.meeting_count(),
+                    fetched.date(),
+                    fetched.hour(),
+                    fetched.minute()
+                );
+            }
+            None => println!("{source}  not fetched yet"),
+        }
+    }
+    if prefs.catalog_sources.is_empty() {
+        println!("No shared catalogs, add one with `jogger catalogs add <source>`");
+    }
+
+    prefs.save().map_err(|err| err.to_string())
+}
+
+// Refreshing never fails outright, sources that can't be read keep their last copy
+fn report_refresh(prefs: &mut Preferences) {
+    for err in prefs.refresh_catalogs() {
+        eprintln!("warning: {err}");
+    }
+}
+
+fn show_config(prefs: &Preferences) {
+    for (key, value, origin) in prefs.settings() {
+        println!("{key:<18} {value:<40} {origin}");
+    }
+}
+
+fn show_profiles(prefs: &Preferences, ticket: Option<&str>) {
+    if let Some(ticket) = ticket {
+        let site = prefs.for_ticket(ticket);
+        println!(
+            "{} goes to {}",
+            prefs.describe_ticket(ticket),
+            site.jira_url
+        );
+        return;
+    }
+
+    println!("{:<12} {:<40} everything else", "main", prefs.jira_url);
+    for profile in &prefs.profiles {
+        println!(
+            "{:<12} {:<40} {}",
+            profile.name,
+            profile.jira_url,
+            profile.prefixes.join(", ")
+        );
+    }
+    for problem in profile_problems(&prefs.profiles) {
+        eprintln!("warning: {problem}");
+    }
+}
+
+fn show_worklogs(prefs: &Preferences, week: bool) -> Result<(), String> {
+    let today = local_now().date();
+    let (from, to) = if week { week_of(today) } else { (today, today) };
+
+    let worklogs = prefs.session().fetch_worklogs(from, to).map_err(describe)?;
+
+    for worklog in &worklogs {
+        println!(
+            "{} {:02}:{:02}  {:<12} {:>6.2}h  {}",
+            worklog.started.date(),
+            worklog.started.hour(),
+            worklog.started.minute(),
+            worklog.ticket,
+            worklog.seconds as f64 / 3600.0,
+            worklog.comment
+        );
+    }
+
+    let total: usize = worklogs.iter().map(|worklog| worklog.seconds).sum();
+    println!("Total: {:.2}h", total as f64 / 3600.0);
+    Ok(())
+}
+
+fn describe(err: JiraError) -> String {
+    match err {
+        JiraError::Unauthorized => {
+            format!("{err}, update your credentials by running `jogger` and opening Setup")
+        }
+        _ => err.to_string(),
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{parse, take_options, CatalogAction, Command, Started};
+    use std::path::PathBuf;
+
+    fn args(args: &[&str]) -> Vec<String> {
+        args.iter().map(|arg| arg.to_string()).collect()
+    }
+
+    #[test]
+    fn log_with_message() {
+        let command = parse(&args(&[
+            "log",
+            "PROJ-123",
+            "1h30",
+            "-m",
+            "fixed import bug",
+        ]));
+        assert_eq!(
+            command,
+            Ok(Command::Log {
+                ticket: "PROJ-123".to_string(),
+                time: "1h30".to_string(),
+                comment: "fixed import bug".to_string(),
+                started: Started::default(),
+            })
+        );
+    }
+
+    #[test]
+    fn distraction_without_message() {
+        let command = parse(&args(&["distraction", "PIM", "billable", "15m"]));
+        assert_eq!(
+            command,
+            Ok(Command::Distraction {
+                project: "PIM".to_string(),
+                meeting: "billable".to_string(),
+                time: "15m".to_string(),
+                comment: String::new(),
+                started: Started::default(),
+            })
+        );
+    }
+
+    #[test]
+    fn log_with_custom_start() {
+        let command = parse(&args(&[
+            "log",
+            "PROJ-123",
+            "--date",
+            "yesterday",
+            "15m",
+            "-s",
+            "9:30",
+        ]));
+        assert_eq!(
+            command,
+            Ok(Command::Log {
+                ticket: "PROJ-123".to_string(),
+                time: "15m".to_string(),
+                comment: String::new(),
+                started: Started {
+                    date: "yesterday".to_string(),
+                    start: "9:30".to_string(),
+                },
+            })
+        );
+    }
+
+    #[test]
+    fn catalog_actions() {
+        assert_eq!(
+            parse(&args(&["catalogs"])),
+            Ok(Command::Catalogs(CatalogAction::List))
+        );
+        assert_eq!(
+            parse(&args(&["catalogs", "add", "https://example.com/team.json"])),
+            Ok(Command::Catalogs(CatalogAction::Add(
+                "https://example.com/team.json".to_string()
+            )))
+        );
+        assert!(parse(&args(&["catalogs", "add"])).is_err());
+    }
+
+    #[test]
+    fn profiles_take_an_optional_ticket() {
+        assert_eq!(
+            parse(&args(&["profiles"])),
+            Ok(Command::Profiles { ticket: None })
+        );
+        assert_eq!(
+            parse(&args(&["profiles", "ACME-1"])),
+            Ok(Command::Profiles {
+                ticket: Some("ACME-1".to_string())
+            })
+        );
+    }
+
+    #[test]
+    fn options_are_taken_out() {
+        let mut given = args(&[
+            "--set",
+            "jira_url=https://jira.test",
+            "log",
+            "PROJ-1",
+            "--set=rounding=up:15",
+            "--config",
+            "/tmp/work.conf",
+            "1h",
+        ]);
+        let options = take_options(&mut given).unwrap();
+
+        assert_eq!(given, args(&["log", "PROJ-1", "1h"]));
+        assert_eq!(options.config, Some(PathBuf::from("/tmp/work.conf")));
+        assert_eq!(
+            options.overrides,
+            vec![
+                ("jira_url".to_string(), "https://jira.test".to_string()),
+                ("rounding".to_string(), "up:15".to_string()),
+            ]
+        );
+        assert!(take_options(&mut args(&["--set", "timer_state=1"])).is_err());
+        assert!(take_options(&mut args(&["--config"])).is_err());
+    }
+
+    #[test]
+    fn missing_arguments() {
+        assert!(parse(&args(&["log", "PROJ-123"])).is_err());
+        assert!(parse(&args(&["log", "PROJ-123", "1h", "-m"])).is_err());
+        assert!(parse(&args(&["log", "PROJ-123", "1h", "--date"])).is_err());
+    }
+
+    #[test]
+    fn unknown_command() {
+        assert!(parse(&args(&["submit", "PROJ-123", "1h"])).is_err());
+    }
+}
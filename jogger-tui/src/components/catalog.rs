@@ -0,0 +1,602 @@
+use cursive::{
+    view::{Nameable, Resizable},
+    views::{Button, Dialog, EditView, LinearLayout, Panel, SelectView, TextView},
+    Cursive, View,
+};
+use jogger_core::{
+    catalog_problems, load_catalog, CatalogError, JiraError, JiraSession, Meeting, PrefRef,
+    Preferences, Project,
+};
+use std::{cell::RefCell, rc::Rc};
+
+use crate::components::{create_message_dialog, is_on_top, renew_session, session, Shared};
+
+// The catalog being edited, only written to the preferences on save
+type Draft = Rc<RefCell<Vec<Project>>>;
+
+const PROJECTS: &str = "catalog_projects";
+const MEETINGS: &str = "catalog_meetings";
+const LIST_HEIGHT: usize = 10;
+const CHECKING: &str = "catalog_checking";
+
+pub fn create_catalog_dialog(prefs: PrefRef, width: usize) -> Box<dyn View> {
+    let draft: Draft = Rc::new(RefCell::new(prefs.borrow().custom_meetings.clone()));
+
+    let mut projects = SelectView::new();
+    let mut meetings = SelectView::new();
+    list_projects(&mut projects, &draft.borrow());
+    list_meetings(&mut meetings, draft.borrow().first());
+
+    let d = Rc::clone(&draft);
+    let projects = projects.on_select(move |c, project| {
+        let project = *project;
+        let projects = d.borrow();
+        c.call_on_name(MEETINGS, |view: &mut SelectView<usize>| {
+            list_meetings(view, projects.get(project))
+        });
+    });
+
+    let project_buttons = buttons(
+        &draft,
+        width,
+        [
+            ("Add", |c, draft, width| {
+                c.add_layer(create_project_dialog(Rc::clone(draft), None, width))
+            }),
+            ("Rename", |c, draft, width| {
+                if let Some(project) = selected(c, PROJECTS) {
+                    c.add_layer(create_project_dialog(
+                        Rc::clone(draft),
+                        Some(project),
+                        width,
+                    ))
+                }
+            }),
+            ("Up", |c, draft, _| move_project(c, draft, true)),
+            ("Down", |c, draft, _| move_project(c, draft, false)),
+            ("Delete", |c, draft, width| {
+                if let Some(project) = selected(c, PROJECTS) {
+                    c.add_layer(create_delete_project_dialog(
+                        Rc::clone(draft),
+                        project,
+                        width,
+                    ))
+                }
+            }),
+        ],
+    );
+
+    let meeting_buttons = buttons(
+        &draft,
+        width,
+        [
+            ("Add", |c, draft, width| match selected(c, PROJECTS) {
+                Some(project) => c.add_layer(create_meeting_dialog(
+                    Rc::clone(draft),
+                    project,
+                    None,
+                    width,
+                )),
+                None => c.add_layer(create_message_dialog("Add a project first", 1, width)),
+            }),
+            ("Edit", |c, draft, width| {
+                if let (Some(project), Some(meeting)) =
+                    (selected(c, PROJECTS), selected(c, MEETINGS))
+                {
+                    c.add_layer(create_meeting_dialog(
+                        Rc::clone(draft),
+                        project,
+                        Some(meeting),
+                        width,
+                    ))
+                }
+            }),
+            ("Up", |c, draft, _| move_meeting(c, draft, true)),
+            ("Down", |c, draft, _| move_meeting(c, draft, false)),
+            ("Delete", |c, draft, _| {
+                if let (Some(project), Some(meeting)) =
+                    (selected(c, PROJECTS), selected(c, MEETINGS))
+                {
+                    draft.borrow_mut()[project].meetings.remove(meeting);
+                    refresh(c, draft, project, meeting);
+                }
+            }),
+        ],
+    );
+
+    let view = LinearLayout::horizontal()
+        .child(
+            LinearLayout::vertical()
+                .child(
+                    Panel::new(projects.with_name(PROJECTS))
+                        .title("Projects")
+                        .fixed_height(LIST_HEIGHT),
+                )
+                .child(project_buttons)
+                .fixed_width(width / 2),
+        )
+        .child(
+            LinearLayout::vertical()
+                .child(
+                    Panel::new(meetings.with_name(MEETINGS))
+                        .title("Meetings")
+                        .fixed_height(LIST_HEIGHT),
+                )
+                .child(meeting_buttons)
+                .fixed_width(width / 2),
+        );
+
+    let view = LinearLayout::vertical().child(view).child(TextView::new(
+        "Meetings from shared catalogs are merged in when logging and are not listed here.",
+    ));
+
+    let p = Rc::clone(&prefs);
+    Box::from(
+        Dialog::around(view)
+            .title("Meeting Tickets")
+            .button("Shared Catalogs", move |c| {
+                c.add_layer(create_shared_catalogs_dialog(Rc::clone(&p), width))
+            })
+            .button("Save", move |c| {
+                let projects = draft.borrow().clone();
+
+                let problems = catalog_problems(&projects);
+                if !problems.is_empty() {
+                    c.add_layer(create_message_dialog(&problems.join("\n"), 1, width));
+                    return;
+                }
+
+                let session = session(c);
+                let sink = c.cb_sink().clone();
+                c.add_layer(
+                    Dialog::around(TextView::new("Checking the tickets in Jira..."))
+                        .button("Cancel", |c| {
+                            c.pop_layer();
+                        })
+                        .fixed_width(width)
+                        .with_name(CHECKING),
+                );
+
+                std::thread::spawn(move || {
+                    let unknown = check_tickets(&session, &projects);
+                    sink.send(Box::new(move |c| finish_check(c, projects, unknown, width)))
+                        .ok();
+                });
+            })
+            .button("Cancel", |c| {
+                c.pop_layer();
+            })
+            .fixed_width(width),
+    )
+}
+
+type Action = fn(&mut Cursive, &Draft, usize);
+
+fn buttons(draft: &Draft, width: usize, actions: [(&str, Action); 5]) -> LinearLayout {
+    let mut layout = LinearLayout::horizontal();
+    for (label, action) in actions {
+        let draft = Rc::clone(draft);
+        layout.add_child(Button::new(label, move |c| action(c, &draft, width)));
+    }
+    layout
+}
+
+fn list_projects(view: &mut SelectView<usize>, projects: &[Project]) {
+    view.clear();
+    for (i, project) in projects.iter().enumerate() {
+        view.add_item(project.name.clone(), i);
+    }
+}
+
+fn list_meetings(view: &mut SelectView<usize>, project: Option<&Project>) {
+    view.clear();
+    for (i, meeting) in project.into_iter().flat_map(|p| &p.meetings).enumerate() {
+        let billable = match meeting.billable {
+            Some(true) => " (billable)",
+            Some(false) => " (non-billable)",
+            None => "",
+        };
+        view.add_item(
+            format!("{} - {}{billable}", meeting.label, meeting.ticket),
+            i,
+        );
+    }
+}
+
+fn selected(c: &mut Cursive, name: &str) -> Option<usize> {
+    c.find_name::<SelectView<usize>>(name)
+        .and_then(|view| view.selection())
+        .map(|index| *index)
+}
+
+// Lists the draft again after a change, keeping the cursor on what was changed
+fn refresh(c: &mut Cursive, draft: &Draft, project: usize, meeting: usize) {
+    let projects = draft.borrow();
+    let project = project.min(projects.len().saturating_sub(1));
+
+    c.call_on_name(PROJECTS, |view: &mut SelectView<usize>| {
+        list_projects(view, &projects);
+        view.set_selection(project);
+    });
+    c.call_on_name(MEETINGS, |view: &mut SelectView<usize>| {
+        list_meetings(view, projects.get(project));
+        view.set_selection(meeting);
+    });
+}
+
+// Swaps an item with its neighbour, returning where it ended up
+fn shift<T>(items: &mut [T], index: usize, up: bool) -> usize {
+    let target = if up {
+        index.saturating_sub(1)
+    } else {
+        (index + 1).min(items.len().saturating_sub(1))
+    };
+    items.swap(index, target);
+    target
+}
+
+fn move_project(c: &mut Cursive, draft: &Draft, up: bool) {
+    if let Some(project) = selected(c, PROJECTS) {
+        let project = shift(&mut draft.borrow_mut(), project, up);
+        refresh(c, draft, project, 0);
+    }
+}
+
+fn move_meeting(c: &mut Cursive, draft: &Draft, up: bool) {
+    if let (Some(project), Some(meeting)) = (selected(c, PROJECTS), selected(c, MEETINGS)) {
+        let meeting = shift(&mut draft.borrow_mut()[project].meetings, meeting, up);
+        refresh(c, draft, project, meeting);
+    }
+}
+
+fn create_project_dialog(draft: Draft, project: Option<usize>, width: usize) -> Box<dyn View> {
+    let name = project
+        .map(|project| draft.borrow()[project].name.clone())
+        .unwrap_or_default();
+
+    Box::from(
+        Dialog::around(
+            LinearLayout::horizontal()
+                .child(TextView::new("Name: "))
+                .child(
+                    EditView::new()
+                        .content(name)
+                        .with_name("catalog_project_name")
+                        .full_width(),
+                ),
+        )
+        .title(if project.is_some() {
+            "Rename Project"
+        } else {
+            "Add Project"
+        })
+        .button("Okay", move |c| {
+            let name = content(c, "catalog_project_name");
+            if name.is_empty() {
+                c.add_layer(create_message_dialog("A project needs a name", 1, width));
+                return;
+            }
+
+            let project = {
+                let mut projects = draft.borrow_mut();
+                match project {
+                    Some(project) => {
+                        projects[project].name = name;
+                        project
+                    }
+                    None => {
+                        projects.push(Project::new(&name, Vec::new()));
+                        projects.len() - 1
+                    }
+                }
+            };
+            c.pop_layer();
+            refresh(c, &draft, project, 0);
+        })
+        .button("Cancel", |c| {
+            c.pop_layer();
+        })
+        .fixed_width(width),
+    )
+}
+
+fn create_delete_project_dialog(draft: Draft, project: usize, width: usize) -> Box<dyn View> {
+    let message = {
+        let project = &draft.borrow()[project];
+        format!(
+            "Delete `{}` and its {} meeting(s)?",
+            project.name,
+            project.meetings.len()
+        )
+    };
+
+    Box::from(
+        Dialog::around(TextView::new(message))
+            .button("Delete", move |c| {
+                draft.borrow_mut().remove(project);
+                c.pop_layer();
+                refresh(c, &draft, project, 0);
+            })
+            .button("Cancel", |c| {
+                c.pop_layer();
+            })
+            .fixed_width(width),
+    )
+}
+
+fn create_meeting_dialog(
+    draft: Draft,
+    project: usize,
+    meeting: Option<usize>,
+    width: usize,
+) -> Box<dyn View> {
+    let current = meeting
+        .map(|meeting| draft.borrow()[project].meetings[meeting].clone())
+        .unwrap_or_else(|| Meeting::new("", ""));
+
+    let mut billable = SelectView::new()
+        .popup()
+        .item("Not tracked", None)
+        .item("Billable", Some(true))
+        .item("Non-billable", Some(false));
+    billable.set_selection(match current.billable {
+        None => 0,
+        Some(true) => 1,
+        Some(false) => 2,
+    });
+
+    let view = LinearLayout::vertical()
+        .child(
+            LinearLayout::horizontal()
+                .child(TextView::new("Label: "))
+                .child(
+                    EditView::new()
+                        .content(current.label)
+                        .with_name("catalog_meeting_label")
+                        .full_width(),
+                ),
+        )
+        .child(
+            LinearLayout::horizontal()
+                .child(TextView::new("Ticket: "))
+                .child(
+                    EditView::new()
+                        .content(current.ticket)
+                        .with_name("catalog_meeting_ticket")
+                        .full_width(),
+                ),
+        )
+        .child(
+            LinearLayout::horizontal()
+                .child(TextView::new("Billing: "))
+                .child(billable.with_name("catalog_meeting_billable")),
+        );
+
+    Box::from(
+        Dialog::around(view)
+            .title(if meeting.is_some() {
+                "Edit Meeting"
+            } else {
+                "Add Meeting"
+            })
+            .button("Okay", move |c| {
+                let label = content(c, "catalog_meeting_label");
+                let ticket = content(c, "catalog_meeting_ticket").to_uppercase();
+                if label.is_empty() || ticket.is_empty() {
+                    c.add_layer(create_message_dialog(
+                        "A meeting needs a label and a ticket",
+                        1,
+                        width,
+                    ));
+                    return;
+                }
+
+                let updated = Meeting {
+                    billable: c
+                        .find_name::<SelectView<Option<bool>>>("catalog_meeting_billable")
+                        .and_then(|view| view.selection())
+                        .and_then(|billable| *billable),
+                    ..Meeting::new(&label, &ticket)
+                };
+
+                let meeting = {
+                    let mut projects = draft.borrow_mut();
+                    let meetings = &mut projects[project].meetings;
+                    match meeting {
+                        Some(meeting) => {
+                            meetings[meeting] = updated;
+                            meeting
+                        }
+                        None => {
+                            meetings.push(updated);
+                            meetings.len() - 1
+                        }
+                    }
+                };
+                c.pop_layer();
+                refresh(c, &draft, project, meeting);
+            })
+            .button("Cancel", |c| {
+                c.pop_layer();
+            })
+            .fixed_width(width),
+    )
+}
+
+const SOURCES: &str = "catalog_sources";
+
+fn create_shared_catalogs_dialog(prefs: PrefRef, width: usize) -> Box<dyn View> {
+    let mut sources = SelectView::new();
+    list_sources(&mut sources, &prefs.borrow());
+
+    let view = LinearLayout::vertical()
+        .child(Panel::new(sources.with_name(SOURCES)).fixed_height(LIST_HEIGHT))
+        .child(
+            LinearLayout::horizontal()
+                .child(TextView::new("File or URL: "))
+                .child(EditView::new().with_name("catalog_source").full_width()),
+        );
+
+    let (add, remove, refresh) = (Rc::clone(&prefs), Rc::clone(&prefs), prefs);
+    Box::from(
+        Dialog::around(view)
+            .title("Shared Catalogs")
+            .button("Add", move |c| {
+                let source = content(c, "catalog_source");
+                if source.is_empty() || add.borrow().catalog_sources.contains(&source) {
+                    return;
+                }
+                // Check the catalog can be read before keeping it
+                let projects = match load_catalog(&source) {
+                    Ok(projects) => projects,
+                    Err(err) => {
+                        c.add_layer(create_message_dialog(&format!("ERROR: {err}"), 1, width));
+                        return;
+                    }
+                };
+
+                let problems = add.borrow_mut().add_catalog(&source, projects).err();
+                save_sources(c, &add, problems.into_iter().collect(), width);
+                c.call_on_name("catalog_source", |view: &mut EditView| {
+                    view.set_content("");
+                });
+            })
+            .button("Remove", move |c| {
+                let selection = c
+                    .find_name::<SelectView<String>>(SOURCES)
+                    .and_then(|view| view.selection());
+                if let Some(source) = selection {
+                    let problems = remove.borrow_mut().remove_catalog(&source).err();
+                    save_sources(c, &remove, problems.into_iter().collect(), width);
+                }
+            })
+            .button("Refresh", move |c| {
+                let problems = refresh.borrow_mut().refresh_catalogs();
+                save_sources(c, &refresh, problems, width);
+            })
+            .button("Close", |c| {
+                c.pop_layer();
+            })
+            .fixed_width(width),
+    )
+}
+
+fn list_sources(view: &mut SelectView<String>, prefs: &Preferences) {
+    view.clear();
+    for source in &prefs.catalog_sources {
+        let status = match prefs.shared_catalogs.iter().find(|c| &c.source == source) {
+            Some(catalog) => {
+                let fetched = catalog.fetched();
+                format!(
+                    "{} meeting(s), fetched {} {:02}:{:02}",
+                    catalog.meeting_count(),
+                    fetched.date(),
+                    fetched.hour(),
+                    fetched.minute()
+                )
+            }
+            None => "not fetched yet".to_string(),
+        };
+        view.add_item(format!("{source}  {status}"), source.clone());
+    }
+}
+
+// Saves the list of sources and shows it again, along with anything that went wrong with it
+fn save_sources(c: &mut Cursive, prefs: &PrefRef, problems: Vec<CatalogError>, width: usize) {
+    let mut prefs = prefs.borrow_mut();
+    let mut problems: Vec<String> = problems.iter().map(|err| err.to_string()).collect();
+    if let Err(err) = prefs.save() {
+        problems.push(format!("An error occured: {err}"));
+    }
+
+    c.call_on_name(SOURCES, |view: &mut SelectView<String>| {
+        list_sources(view, &prefs)
+    });
+    drop(prefs);
+
+    // Meetings from the catalogs decide how their tickets are rounded
+    renew_session(c);
+    if !problems.is_empty() {
+        c.add_layer(create_message_dialog(&problems.join("\n"), 1, width));
+    }
+}
+
+fn content(c: &mut Cursive, name: &str) -> String {
+    c.find_name::<EditView>(name)
+        .map(|view| view.get_content().trim().to_string())
+        .unwrap_or_default()
+}
+
+// Asks Jira about every ticket, describing the ones it doesn't know. Other errors mean nothing can
+// be checked, so they are reported once instead of for every ticket.
+fn check_tickets(session: &JiraSession, projects: &[Project]) -> Vec<String> {
+    let mut tickets: Vec<&str> = Vec::new();
+    for meeting in projects.iter().flat_map(|project| &project.meetings) {
+        if !tickets.contains(&meeting.ticket.as_str()) {
+            tickets.push(&meeting.ticket);
+        }
+    }
+
+    let mut problems = Vec::new();
+    for ticket in tickets {
+        match session.fetch_issue(ticket) {
+            Ok(_) => {}
+            Err(err @ JiraError::IssueNotFound { .. }) => problems.push(err.to_string()),
+            Err(err) => return vec![format!("Could not check the tickets in Jira: {err}")],
+        }
+    }
+    problems
+}
+
+// Saves once the tickets are checked, or asks first if some are unknown. Does nothing if the
+// check was cancelled.
+fn finish_check(c: &mut Cursive, projects: Vec<Project>, unknown: Vec<String>, width: usize) {
+    let on_top = is_on_top(c, CHECKING);
+    let Some(prefs) = c
+        .user_data::<Shared>()
+        .map(|shared| Rc::clone(&shared.prefs))
+    else {
+        return;
+    };
+    if !on_top {
+        return;
+    }
+
+    c.pop_layer();
+    if unknown.is_empty() {
+        save(c, &prefs, projects, width);
+        return;
+    }
+
+    c.add_layer(
+        Dialog::around(TextView::new(unknown.join("\n")))
+            .title("Check Tickets")
+            .button("Save Anyway", move |c| {
+                c.pop_layer();
+                save(c, &prefs, projects.clone(), width);
+            })
+            .button("Back", |c| {
+                c.pop_layer();
+            })
+            .fixed_width(width),
+    );
+}
+
+fn save(c: &mut Cursive, prefs: &PrefRef, projects: Vec<Project>, width: usize) {
+    let mut prefs = prefs.borrow_mut();
+    prefs.custom_meetings = projects;
+    let saved = prefs.save();
+    drop(prefs);
+
+    renew_session(c);
+    match saved {
+        Ok(_) => {
+            c.pop_layer();
+        }
+        Err(err) => c.add_layer(create_message_dialog(
+            &format!("An error occured: {err}"),
+            1,
+            width,
+        )),
+    }
+}
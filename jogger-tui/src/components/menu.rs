@@ -1,4 +1,7 @@
-use crate::components::{create_issue_input_dialog, create_meetings_dialog, create_setup_dialog};
+use crate::components::{
+    create_catalog_dialog, create_issue_input_dialog, create_meetings_dialog, create_queue_dialog,
+    create_setup_dialog, create_undo_dialog, session, show_week_dialog,
+};
 use cursive::view::Resizable;
 use cursive::views::{Dialog, SelectView, TextView};
 use cursive::View;
@@ -9,8 +12,12 @@ pub fn create_menu_dialog(prefs: PrefRef, width: usize) -> Box<dyn View> {
     let menu = SelectView::new()
         .item("Log Time to a Ticket", 1)
         .item("Log Personal Distraction", 2)
-        .item("Setup", 3)
-        .item("Quit", 4)
+        .item("Undo Last Log", 3)
+        .item("My Week", 4)
+        .item("Pending Worklogs", 5)
+        .item("Meeting Tickets", 6)
+        .item("Setup", 7)
+        .item("Quit", 8)
         .on_submit(move |c, item| {
             let prefs = Rc::clone(&prefs);
             match item {
@@ -20,8 +27,15 @@ pub fn create_menu_dialog(prefs: PrefRef, width: usize) -> Box<dyn View> {
                     Some("Log Personal Distraction"),
                     width,
                 )),
-                3 => c.add_layer(create_setup_dialog(prefs, width)),
-                4 => c.quit(),
+                3 => {
+                    let session = session(c);
+                    c.add_layer(create_undo_dialog(prefs, &session, width))
+                }
+                4 => show_week_dialog(c, width),
+                5 => c.add_layer(create_queue_dialog(prefs, width)),
+                6 => c.add_layer(create_catalog_dialog(prefs, width)),
+                7 => c.add_layer(create_setup_dialog(prefs, width)),
+                8 => c.quit(),
                 _ => c.add_layer(
                     Dialog::around(TextView::new("This function has not yet been implemented."))
                         .button("Okay", |c| {
@@ -0,0 +1,18 @@
+use cursive::{
+    view::Resizable,
+    views::{Dialog, TextView},
+    View,
+};
+
+/// A dialog that closes itself along with the `layers - 1` layers beneath it.
+pub fn create_message_dialog(message: &str, layers: usize, width: usize) -> Box<dyn View> {
+    Box::from(
+        Dialog::around(TextView::new(message))
+            .button("Okay", move |c| {
+                for _ in 0..layers {
+                    c.pop_layer();
+                }
+            })
+            .fixed_width(width),
+    )
+}
@@ -1,7 +1,62 @@
+mod catalog;
 mod menu;
+mod message;
+mod queue;
 mod setup;
 mod timelog;
+mod undo;
+mod week;
 
+use cursive::{views::LayerPosition, Cursive};
+use jogger_core::{JiraSession, PrefRef};
+
+pub use catalog::create_catalog_dialog;
 pub use menu::create_menu_dialog;
+pub use message::create_message_dialog;
+pub use queue::create_queue_dialog;
 pub use setup::create_setup_dialog;
-pub use timelog::{create_issue_input_dialog, create_meetings_dialog};
+pub use timelog::{create_issue_input_dialog, create_log_on_day_dialog, create_meetings_dialog};
+pub use undo::create_undo_dialog;
+pub use week::{refresh_week_dialog, show_week_dialog};
+
+/// What every view shares, kept as Cursive's user data so any callback can reach it.
+pub struct Shared {
+    pub prefs: PrefRef,
+    /// Talks to Jira for every view until the settings change.
+    pub session: JiraSession,
+}
+
+impl Shared {
+    pub fn new(prefs: PrefRef) -> Self {
+        let session = prefs.borrow().session();
+        Shared { prefs, session }
+    }
+
+    /// Makes the session again from the settings as they are now, keeping its HTTP client.
+    pub fn renew_session(&mut self) {
+        self.session = self.session.with_preferences(self.prefs.borrow().clone());
+    }
+}
+
+/// The session shared by every view, see [`Shared`].
+pub fn session(c: &mut Cursive) -> JiraSession {
+    c.with_user_data(|shared: &mut Shared| shared.session.clone())
+        .expect("the shared state is set up before any view")
+}
+
+/// Whether the layer called `name` is the one on top, e.g. to tell if a dialog is still open
+/// when something it waited for arrives.
+pub fn is_on_top(c: &mut Cursive, name: &str) -> bool {
+    let screen = c.screen_mut();
+    match screen.find_layer_from_name(name) {
+        Some(LayerPosition::FromFront(index)) => index == 0,
+        Some(LayerPosition::FromBack(index)) => index + 1 == screen.len(),
+        None => false,
+    }
+}
+
+/// Brings the shared session up to date after the settings were changed here, rather than
+/// waiting for the change to be picked up from the file.
+pub fn renew_session(c: &mut Cursive) {
+    c.with_user_data(Shared::renew_session);
+}
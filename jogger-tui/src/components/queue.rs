@@ -0,0 +1,248 @@
+use cursive::{
+    view::{Nameable, Resizable, Scrollable},
+    views::{Dialog, EditView, LinearLayout, SelectView, TextView},
+    Cursive, View,
+};
+use jogger_core::{
+    local_now, replay_queue, retry_queued, seconds_to_string, JiraSession, PrefRef, Queue,
+    QueuedLog, RetryError,
+};
+use std::rc::Rc;
+use time::OffsetDateTime;
+
+use crate::components::{create_message_dialog, is_on_top, session, Shared};
+
+const SUBMITTING: &str = "submitting";
+
+pub fn create_queue_dialog(prefs: PrefRef, width: usize) -> Box<dyn View> {
+    let queue = match Queue::load() {
+        Ok(queue) => queue,
+        Err(err) => return create_message_dialog(&format!("ERROR: {err}"), 1, width),
+    };
+
+    if queue.is_empty() {
+        return create_message_dialog("There are no pending worklogs.", 1, width);
+    }
+
+    let mut list = SelectView::new();
+    queue
+        .entries
+        .iter()
+        .for_each(|entry| list.add_item(describe(entry), entry.id.clone()));
+
+    let p = Rc::clone(&prefs);
+    list.set_on_submit(move |c, id: &String| {
+        c.add_layer(create_entry_dialog(Rc::clone(&p), id.clone(), width))
+    });
+
+    Box::from(
+        Dialog::around(list.scrollable())
+            .title("Pending Worklogs")
+            .button("Retry All", move |c| {
+                submit_in_background(c, width, |session| match replay_queue(session) {
+                    Ok(report) => format!(
+                        "Submitted {} worklog(s), {} still pending",
+                        report.submitted.len(),
+                        report.remaining
+                    ),
+                    Err(err) => format!("ERROR: {err}"),
+                });
+            })
+            .button("Close", |c| {
+                c.pop_layer();
+            })
+            .fixed_width(width),
+    )
+}
+
+fn create_entry_dialog(prefs: PrefRef, id: String, width: usize) -> Box<dyn View> {
+    let r = Rc::clone(&prefs);
+    let (retry_id, edit_id) = (id.clone(), id.clone());
+
+    Box::from(
+        Dialog::around(TextView::new(
+            "What would you like to do with this worklog?",
+        ))
+        .button("Retry", move |c| {
+            let id = retry_id.clone();
+            c.pop_layer();
+            submit_in_background(c, width, move |session| match retry_queued(&id, session) {
+                Ok(_) => "Submitted".to_string(),
+                Err(err @ RetryError::NotPending) => err.to_string(),
+                Err(err) => format!("ERROR: {err}"),
+            });
+        })
+        .button("Edit", move |c| {
+            let entry = Queue::load()
+                .ok()
+                .and_then(|queue| queue.get(&edit_id).cloned());
+
+            c.pop_layer();
+            match entry {
+                Some(entry) => c.add_layer(create_edit_dialog(Rc::clone(&r), entry, width)),
+                None => c.add_layer(create_message_dialog(
+                    &RetryError::NotPending.to_string(),
+                    1,
+                    width,
+                )),
+            }
+        })
+        .button("Discard", move |c| {
+            let result = with_queue(|queue| {
+                let pending = queue.entries.len();
+                queue.entries.retain(|entry| entry.id != id);
+                match queue.entries.len() < pending {
+                    true => "Discarded".to_string(),
+                    false => RetryError::NotPending.to_string(),
+                }
+            });
+
+            c.pop_layer();
+            refresh(c, Rc::clone(&prefs), width);
+            c.add_layer(create_message_dialog(&result, 1, width));
+        })
+        .button("Cancel", |c| {
+            c.pop_layer();
+        })
+        .fixed_width(width),
+    )
+}
+
+fn create_edit_dialog(prefs: PrefRef, entry: QueuedLog, width: usize) -> Box<dyn View> {
+    let field = |label: &str, name: &str, content: &str| {
+        LinearLayout::horizontal()
+            .child(TextView::new(label))
+            .child(
+                EditView::new()
+                    .content(content)
+                    .with_name(name)
+                    .full_width(),
+            )
+    };
+
+    let view = LinearLayout::vertical()
+        .child(field(
+            "Issue Number: ",
+            "queue_ticket",
+            &entry.ticket_number,
+        ))
+        .child(field(
+            "Time: ",
+            "queue_time",
+            &seconds_to_string(entry.time_spent_seconds),
+        ))
+        .child(field("Comment: ", "queue_comment", &entry.comment));
+
+    Box::from(
+        Dialog::around(view)
+            .title("Edit Pending Worklog")
+            .button("Save", move |c| {
+                let ticket = c
+                    .find_name::<EditView>("queue_ticket")
+                    .unwrap()
+                    .get_content();
+                let time = c.find_name::<EditView>("queue_time").unwrap().get_content();
+                let comment = c
+                    .find_name::<EditView>("queue_comment")
+                    .unwrap()
+                    .get_content();
+
+                let parsed = prefs.borrow().parse_duration(&time);
+                let seconds = match parsed {
+                    Ok(seconds) => seconds,
+                    Err(err) => {
+                        c.add_layer(create_message_dialog(
+                            &format!("ERROR: {}", err.describe(&time)),
+                            1,
+                            width,
+                        ));
+                        return;
+                    }
+                };
+
+                let result = with_queue(|queue| match queue.get_mut(&entry.id) {
+                    Some(entry) => {
+                        entry.ticket_number = ticket.to_string();
+                        entry.time_spent_seconds = seconds;
+                        entry.comment = comment.to_string();
+                        "Saved".to_string()
+                    }
+                    None => RetryError::NotPending.to_string(),
+                });
+
+                c.pop_layer();
+                refresh(c, Rc::clone(&prefs), width);
+                c.add_layer(create_message_dialog(&result, 1, width));
+            })
+            .button("Cancel", |c| {
+                c.pop_layer();
+            })
+            .fixed_width(width),
+    )
+}
+
+// Applies `action` to the queue while no other jogger can change it and saves it again,
+// describing the outcome
+fn with_queue<F: FnOnce(&mut Queue) -> String>(action: F) -> String {
+    match Queue::update(action) {
+        Ok(message) => message,
+        Err(err) => format!("ERROR: {err}"),
+    }
+}
+
+// Runs `submit` on another thread so Jira doesn't hold up the interface, then refreshes the
+// list of pending worklogs underneath and shows what it returned
+fn submit_in_background<F>(c: &mut Cursive, width: usize, submit: F)
+where
+    F: FnOnce(&JiraSession) -> String + Send + 'static,
+{
+    c.add_layer(
+        Dialog::around(TextView::new("Submitting..."))
+            .fixed_width(width)
+            .with_name(SUBMITTING),
+    );
+
+    let session = session(c);
+    let sink = c.cb_sink().clone();
+    std::thread::spawn(move || {
+        let result = submit(&session);
+        sink.send(Box::new(move |c| {
+            if !is_on_top(c, SUBMITTING) {
+                return;
+            }
+            let Some(prefs) = c
+                .user_data::<Shared>()
+                .map(|shared| Rc::clone(&shared.prefs))
+            else {
+                return;
+            };
+
+            c.pop_layer();
+            refresh(c, prefs, width);
+            c.add_layer(create_message_dialog(&result, 1, width));
+        }))
+        .ok();
+    });
+}
+
+// Replaces the list of pending worklogs with a fresh copy
+fn refresh(c: &mut Cursive, prefs: PrefRef, width: usize) {
+    c.pop_layer();
+    c.add_layer(create_queue_dialog(prefs, width));
+}
+
+fn describe(entry: &QueuedLog) -> String {
+    let started = OffsetDateTime::from_unix_timestamp(entry.started)
+        .map(|started| started.to_offset(local_now().offset()))
+        .unwrap_or_else(|_| local_now());
+
+    format!(
+        "{:<12} {:>7}  {} {:02}:{:02}  {}",
+        entry.ticket_number,
+        seconds_to_string(entry.time_spent_seconds),
+        started.date(),
+        started.hour(),
+        started.minute(),
+        entry.last_error
+    )
+}
@@ -1,14 +1,44 @@
 use cursive::{
     view::{Nameable, Resizable},
-    views::{Dialog, EditView, LinearLayout, TextView, ViewRef},
-    View,
+    views::{Dialog, EditView, LinearLayout, SelectView, TextView, ViewRef},
+    Cursive, View,
 };
-use jogger_core::PrefRef;
+use jogger_core::{AuthMode, PrefRef, Preferences, Rounding};
 use std::rc::Rc;
 
+use crate::components::{create_message_dialog, renew_session, session};
+
 pub fn create_setup_dialog(prefs: PrefRef, width: usize) -> Box<dyn View> {
     let p = Rc::clone(&prefs);
 
+    let mut auth_mode = SelectView::new().popup();
+    AuthMode::ALL
+        .iter()
+        .for_each(|mode| auth_mode.add_item(mode.to_string(), *mode));
+    auth_mode.set_selection(
+        AuthMode::ALL
+            .iter()
+            .position(|mode| *mode == prefs.borrow().auth_mode)
+            .unwrap_or_default(),
+    );
+    // Personal Access Tokens say who you are on their own
+    auth_mode.set_on_submit(|c, mode: &AuthMode| {
+        c.call_on_name("email_addr", |view: &mut EditView| {
+            view.set_enabled(mode.needs_username())
+        });
+    });
+
+    let mut rounding = SelectView::new().popup();
+    Rounding::PRESETS
+        .iter()
+        .for_each(|preset| rounding.add_item(preset.to_string(), *preset));
+    if let Some(index) = Rounding::PRESETS
+        .iter()
+        .position(|preset| *preset == prefs.borrow().rounding)
+    {
+        rounding.set_selection(index);
+    }
+
     let layout = LinearLayout::vertical()
         .child(
             LinearLayout::horizontal()
@@ -22,20 +52,27 @@ pub fn create_setup_dialog(prefs: PrefRef, width: usize) -> Box<dyn View> {
         )
         .child(
             LinearLayout::horizontal()
-                .child(TextView::new("Email Address: "))
+                .child(TextView::new("Sign In With: "))
+                .child(auth_mode.with_name("auth_mode")),
+        )
+        .child(
+            LinearLayout::horizontal()
+                .child(TextView::new("Email or Username: "))
                 .child(
                     EditView::new()
                         .content(&prefs.borrow().email)
+                        .with_enabled(prefs.borrow().auth_mode.needs_username())
                         .with_name("email_addr")
                         .full_width(),
                 ),
         )
         .child(
             LinearLayout::horizontal()
-                .child(TextView::new("API Key: "))
+                .child(TextView::new("API Token or Password: "))
                 .child(
                     EditView::new()
-                        .content(&prefs.borrow().api_key)
+                        .content(prefs.borrow().api_key.expose())
+                        .secret()
                         .with_name("api_key")
                         .full_width(),
                 ),
@@ -49,29 +86,35 @@ pub fn create_setup_dialog(prefs: PrefRef, width: usize) -> Box<dyn View> {
                         .with_name("jira_url")
                         .full_width(),
                 ),
+        )
+        .child(
+            LinearLayout::horizontal()
+                .child(TextView::new("Hours per Workday: "))
+                .child(
+                    EditView::new()
+                        .content(prefs.borrow().workday_hours.to_string())
+                        .with_name("workday_hours")
+                        .fixed_width(6),
+                ),
+        )
+        .child(
+            LinearLayout::horizontal()
+                .child(TextView::new("Rounding: "))
+                .child(rounding.with_name("rounding")),
         );
 
     Box::from(
         Dialog::around(layout)
             .button("Save", move |c| {
                 let prefs = Rc::clone(&p);
-                let name = &*(c.find_name("name").unwrap() as ViewRef<EditView>).get_content();
-                let api_key =
-                    &*(c.find_name("api_key").unwrap() as ViewRef<EditView>).get_content();
-                let jira_url =
-                    &*(c.find_name("jira_url").unwrap() as ViewRef<EditView>).get_content();
-                let email =
-                    &*(c.find_name("email_addr").unwrap() as ViewRef<EditView>).get_content();
-
-                prefs
-                    .borrow_mut()
-                    .set_name(name)
-                    .set_api_key(api_key)
-                    .set_email(email)
-                    .set_jira_url(jira_url);
+                if let Err(err) = read_fields(c, &mut prefs.borrow_mut()) {
+                    c.add_layer(create_message_dialog(&err, 1, width));
+                    return;
+                }
 
-                let prefs = prefs.borrow();
-                match prefs.save() {
+                let saved = prefs.borrow_mut().save();
+                renew_session(c);
+                match saved {
                     Ok(_) => {
                         c.pop_layer();
                     }
@@ -80,6 +123,21 @@ pub fn create_setup_dialog(prefs: PrefRef, width: usize) -> Box<dyn View> {
                     )))),
                 }
             })
+            .button("Test Connection", move |c| {
+                // Test what has been typed without saving it
+                let mut candidate = prefs.borrow().clone();
+
+                let message = match read_fields(c, &mut candidate).and_then(|_| {
+                    session(c)
+                        .with_preferences(candidate)
+                        .test_connection()
+                        .map_err(|err| format!("ERROR: {err}"))
+                }) {
+                    Ok(user) => format!("Connected to Jira as {user}"),
+                    Err(err) => err,
+                };
+                c.add_layer(create_message_dialog(&message, 1, width));
+            })
             .button("Cancel", |c| {
                 c.pop_layer();
             })
@@ -88,3 +146,45 @@ pub fn create_setup_dialog(prefs: PrefRef, width: usize) -> Box<dyn View> {
             .fixed_width(width),
     )
 }
+
+fn read_fields(c: &mut Cursive, prefs: &mut Preferences) -> Result<(), String> {
+    let name = &*(c.find_name("name").unwrap() as ViewRef<EditView>).get_content();
+    let api_key = &*(c.find_name("api_key").unwrap() as ViewRef<EditView>).get_content();
+    let jira_url = &*(c.find_name("jira_url").unwrap() as ViewRef<EditView>).get_content();
+    let email = &*(c.find_name("email_addr").unwrap() as ViewRef<EditView>).get_content();
+    let auth_mode = c
+        .find_name::<SelectView<AuthMode>>("auth_mode")
+        .and_then(|view| view.selection())
+        .map(|mode| *mode)
+        .unwrap_or_default();
+    if auth_mode.needs_username() && email.trim().is_empty() {
+        return Err(format!("ERROR: {auth_mode} needs your email or username"));
+    }
+    // Keep a custom rounding from the config file unless another one was picked
+    let rounding = c
+        .find_name::<SelectView<Rounding>>("rounding")
+        .and_then(|view| view.selection())
+        .map(|rounding| *rounding)
+        .unwrap_or(prefs.rounding);
+    let workday_hours =
+        &*(c.find_name("workday_hours").unwrap() as ViewRef<EditView>).get_content();
+    let workday_hours = match workday_hours.trim().parse::<f64>() {
+        Ok(hours) if hours > 0.0 && hours <= 24.0 => hours,
+        _ => {
+            return Err(format!(
+                "ERROR: `{workday_hours}` is not a number of hours between 0 and 24"
+            ))
+        }
+    };
+
+    prefs
+        .set_name(name)
+        .set_auth_mode(auth_mode)
+        .set_api_key(api_key)
+        .set_email(email)
+        .set_jira_url(jira_url)
+        .set_workday_hours(workday_hours)
+        .set_rounding(rounding);
+
+    Ok(())
+}
@@ -1,13 +1,26 @@
 use cursive::{
-    view::{Nameable, Resizable},
-    views::{Dialog, DummyView, EditView, LinearLayout, Panel, SelectView, TextView, ViewRef},
+    view::{Nameable, Resizable, Scrollable},
+    views::{Dialog, DummyView, EditView, LinearLayout, Panel, SelectView, TextView},
     Cursive, View,
 };
-use jogger_core::{submit_timelog, Meeting, PrefRef, TimeLog};
+use jogger_core::{
+    recent_tickets, submit_or_queue, Frontend, JiraError, PrefRef, Rounded, Submission, TimeLog,
+};
 use std::rc::Rc;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::time::Duration;
+use time::{Date, OffsetDateTime};
+
+use crate::components::{
+    create_catalog_dialog, create_message_dialog, create_setup_dialog, refresh_week_dialog,
+    session, Shared,
+};
+
+// How long typing has to pause before the issue search asks Jira
+const SEARCH_DELAY: Duration = Duration::from_millis(300);
 
 pub fn create_issue_input_dialog(prefs: PrefRef, width: usize) -> Box<dyn View> {
-    let p = Rc::clone(&prefs);
+    let s = Rc::clone(&prefs);
 
     let submit = |c: &mut Cursive, prefs: PrefRef, width: usize| {
         let issue = c.find_name::<EditView>("issue").unwrap().get_content();
@@ -18,17 +31,49 @@ pub fn create_issue_input_dialog(prefs: PrefRef, width: usize) -> Box<dyn View>
             Some(issue.to_string()),
             width,
             None,
+            None,
         ));
     };
 
-    let view = LinearLayout::horizontal()
-        .child(TextView::new("Issue Number: "))
+    // Until something is typed, offer the tickets logged to most recently
+    let mut results = SelectView::<String>::new();
+    for ticket in recent_tickets(8).unwrap_or_default() {
+        results.add_item(format!("{ticket:<12} (recent)"), ticket);
+    }
+
+    let results = results
+        .on_select(|c, key| {
+            c.call_on_name("issue", |view: &mut EditView| {
+                view.set_content(key.as_str())
+            });
+        })
+        .on_submit(move |c, key: &String| {
+            c.call_on_name("issue", |view: &mut EditView| {
+                view.set_content(key.as_str())
+            });
+            submit(c, Rc::clone(&s), width)
+        })
+        .with_name("issue_results")
+        .scrollable()
+        .fixed_height(8);
+
+    let view = LinearLayout::vertical()
         .child(
-            EditView::new()
-                .on_submit(move |c, _| submit(c, Rc::clone(&p), width))
-                .with_name("issue")
-                .full_width(),
-        );
+            LinearLayout::horizontal()
+                .child(TextView::new("Issue Number: "))
+                .child(
+                    EditView::new()
+                        .on_edit(|c, query, _| search(c, query))
+                        .on_submit({
+                            let prefs = Rc::clone(&prefs);
+                            move |c, _| submit(c, Rc::clone(&prefs), width)
+                        })
+                        .with_name("issue")
+                        .full_width(),
+                ),
+        )
+        .child(TextView::new("").with_name("issue_status"))
+        .child(results);
 
     Box::from(
         Dialog::around(view)
@@ -37,9 +82,74 @@ pub fn create_issue_input_dialog(prefs: PrefRef, width: usize) -> Box<dyn View>
     )
 }
 
+// Looks up issues matching `query` in the background once typing pauses, filling in the results
+// list unless another search has started by the time Jira answers
+fn search(c: &mut Cursive, query: &str) {
+    static LATEST: AtomicUsize = AtomicUsize::new(0);
+    let search = LATEST.fetch_add(1, Ordering::SeqCst) + 1;
+    let is_latest = move || LATEST.load(Ordering::SeqCst) == search;
+
+    if query.trim().len() < 2 {
+        return;
+    }
+
+    let sink = c.cb_sink().clone();
+    let session = session(c);
+    let query = query.to_string();
+
+    std::thread::spawn(move || {
+        std::thread::sleep(SEARCH_DELAY);
+        if !is_latest() {
+            return;
+        }
+
+        let result = session.search_issues(&query);
+
+        sink.send(Box::new(move |c| {
+            if !is_latest() {
+                return;
+            }
+
+            let status = match &result {
+                Ok(issues) if issues.is_empty() => format!("No issues match `{query}`"),
+                Ok(_) => String::new(),
+                Err(err) => format!("ERROR: {err}"),
+            };
+            c.call_on_name("issue_status", |view: &mut TextView| {
+                view.set_content(status)
+            });
+
+            c.call_on_name("issue_results", |view: &mut SelectView<String>| {
+                view.clear();
+                for issue in result.unwrap_or_default() {
+                    view.add_item(
+                        format!("{:<12} {} [{}]", issue.key, issue.summary, issue.status),
+                        issue.key,
+                    );
+                }
+            });
+        }))
+        .ok();
+    });
+}
+
 pub fn create_meetings_dialog(prefs: PrefRef, title: Option<&str>, width: usize) -> Box<dyn View> {
+    let projects = prefs.borrow().meetings();
+    if projects.is_empty() {
+        return Box::from(
+            Dialog::around(TextView::new("No meeting tickets are set up yet."))
+                .button("Set Up Meetings", move |c| {
+                    c.pop_layer();
+                    c.add_layer(create_catalog_dialog(Rc::clone(&prefs), width));
+                })
+                .button("Cancel", |c| {
+                    c.pop_layer();
+                })
+                .fixed_width(width),
+        );
+    }
+
     let mut projects_list = SelectView::new();
-    let projects = prefs.borrow().custom_meetings.clone();
     let height = std::cmp::max(
         projects.iter().map(|p| p.meetings.len()).max(),
         Some(projects.len()),
@@ -56,16 +166,14 @@ pub fn create_meetings_dialog(prefs: PrefRef, title: Option<&str>, width: usize)
     projects[0]
         .meetings
         .iter()
-        .for_each(|Meeting(meeting_type, ticket)| {
-            meetings_list.add_item(meeting_type.to_string(), ticket.to_string())
-        });
+        .for_each(|meeting| meetings_list.add_item(meeting.label.clone(), meeting.ticket.clone()));
 
     projects_list.set_on_select(move |c, item| {
         let mut meeting_list = c.find_name::<SelectView>("meeting").unwrap();
         meeting_list.clear();
         let meetings = &projects[*item].meetings;
-        meetings.iter().for_each(|Meeting(meeting_type, ticket)| {
-            meeting_list.add_item(meeting_type.to_string(), ticket.to_string())
+        meetings.iter().for_each(|meeting| {
+            meeting_list.add_item(meeting.label.clone(), meeting.ticket.clone())
         });
     });
 
@@ -83,7 +191,24 @@ pub fn create_meetings_dialog(prefs: PrefRef, title: Option<&str>, width: usize)
             ),
     );
 
-    create_logging_dialog(prefs, title, None, width, Some(select_meeting))
+    create_logging_dialog(prefs, title, None, width, Some(select_meeting), None)
+}
+
+/// Logging dialog for `issue` with the date already filled in.
+pub fn create_log_on_day_dialog(
+    prefs: PrefRef,
+    issue: String,
+    date: Date,
+    width: usize,
+) -> Box<dyn View> {
+    create_logging_dialog(
+        prefs,
+        Some(&format!("Logging Time for {issue} on {date}")),
+        Some(issue),
+        width,
+        None,
+        Some(date),
+    )
 }
 
 fn create_logging_dialog(
@@ -92,9 +217,14 @@ fn create_logging_dialog(
     issue: Option<String>,
     width: usize,
     child: Option<Box<dyn View>>,
+    date: Option<Date>,
 ) -> Box<dyn View> {
     let i = issue.clone();
     let p = Rc::clone(&prefs);
+    let preview = |prefs: &PrefRef| {
+        let (prefs, issue) = (Rc::clone(prefs), issue.clone());
+        move |c: &mut Cursive, _: &str, _: usize| update_preview(c, &prefs, &issue)
+    };
 
     let child = child.unwrap_or(Box::from(DummyView));
 
@@ -105,21 +235,41 @@ fn create_logging_dialog(
                 .child(TextView::new("Time: "))
                 .child(
                     EditView::new()
-                        .on_submit(move |c, _| submit_time_log(c, Rc::clone(&p), i.clone()))
+                        .on_edit(preview(&prefs))
+                        .on_submit(move |c, _| submit_time_log(c, Rc::clone(&p), i.clone(), width))
                         .with_name("time")
                         .full_width(),
                 ),
         )
+        .child(TextView::new("").with_name("time_preview"))
         .child(
             LinearLayout::horizontal()
                 .child(TextView::new("Comment: "))
                 .child(EditView::new().with_name("comment").full_width()),
+        )
+        .child(
+            LinearLayout::horizontal()
+                .child(TextView::new("Date: "))
+                .child(
+                    EditView::new()
+                        .content(date.map(|date| date.to_string()).unwrap_or_default())
+                        .on_edit(preview(&prefs))
+                        .with_name("date")
+                        .full_width(),
+                )
+                .child(TextView::new(" Start: "))
+                .child(
+                    EditView::new()
+                        .on_edit(preview(&prefs))
+                        .with_name("start")
+                        .fixed_width(7),
+                ),
         );
     Box::from(
         Dialog::around(view)
             .title(title.unwrap_or("Create Time Log"))
             .button("Submit", move |c| {
-                submit_time_log(c, Rc::clone(&prefs), issue.clone())
+                submit_time_log(c, Rc::clone(&prefs), issue.clone(), width)
             })
             .button("Cancel", |c| {
                 c.pop_layer();
@@ -128,52 +278,206 @@ fn create_logging_dialog(
     )
 }
 
-fn submit_time_log(c: &mut Cursive, prefs: PrefRef, issue: Option<String>) {
+// The time, date and start fields as typed
+fn time_fields(c: &mut Cursive) -> (String, String, String) {
+    let content = |c: &mut Cursive, name: &str| {
+        c.find_name::<EditView>(name)
+            .map(|view| view.get_content().to_string())
+            .unwrap_or_default()
+    };
+
+    (content(c, "time"), content(c, "date"), content(c, "start"))
+}
+
+// The ticket chosen in the meetings list, otherwise the one the dialog was opened for
+fn selected_issue(c: &mut Cursive, issue: &Option<String>) -> String {
+    c.find_name::<SelectView>("meeting")
+        .and_then(|view| view.selection().map(|s| s.to_string()))
+        .unwrap_or(issue.clone().unwrap_or_default())
+}
+
+// Shows how the time will be understood while it is being typed
+fn update_preview(c: &mut Cursive, prefs: &PrefRef, issue: &Option<String>) {
+    let (time, date, start) = time_fields(c);
+    let issue = selected_issue(c, issue);
+
+    let preview = if time.trim().is_empty() {
+        String::new()
+    } else {
+        let prefs = prefs.borrow();
+        match prefs.parse_time(&time, &date, &start) {
+            Ok((seconds, started)) => {
+                format!("→ {}", describe_time(prefs.round(&issue, seconds), started))
+            }
+            Err(err) => format!("✗ {}", err.msg()),
+        }
+    };
+
+    c.call_on_name("time_preview", |view: &mut TextView| {
+        view.set_content(preview)
+    });
+}
+
+fn describe_time(time: Rounded, started: Option<OffsetDateTime>) -> String {
+    match started {
+        Some(started) => format!(
+            "{time} starting {} at {:02}:{:02}",
+            started.date(),
+            started.hour(),
+            started.minute()
+        ),
+        None => format!("{time} starting now"),
+    }
+}
+
+fn submit_time_log(c: &mut Cursive, prefs: PrefRef, issue: Option<String>, width: usize) {
     let comment = c
         .find_name::<EditView>("comment")
         .unwrap()
         .get_content()
         .to_string();
 
-    let issue = c
-        .find_name::<SelectView>("meeting")
-        .and_then(|view| view.selection().map(|s| s.to_string()))
-        .unwrap_or(issue.unwrap_or_default());
-
-    let time_input: ViewRef<EditView> = c.find_name("time").unwrap();
-    match jogger_core::string_to_seconds(time_input.get_content().as_str()) {
-        Ok(time) => {
-            c.add_layer(Dialog::around(TextView::new("Uploading...")));
-            // let prefs = prefs.borrow();
-
-            match submit_timelog(&TimeLog {
-                time_spent_seconds: time,
-                comment,
-                ticket_number: issue,
-                prefs,
-            }) {
-                Ok(_) => c.add_layer(
-                    Dialog::around(TextView::new("Successful".to_string())).button("Okay", |c| {
-                        c.pop_layer();
+    let issue = selected_issue(c, &issue);
+    if issue.is_empty() {
+        c.add_layer(create_message_dialog(
+            "Pick a meeting to log time to",
+            1,
+            width,
+        ));
+        return;
+    }
+    let (time_input, date, start) = time_fields(c);
+
+    let parsed = prefs.borrow().parse_time(&time_input, &date, &start);
+    match parsed {
+        Ok((time, started)) => {
+            let rounded = prefs.borrow().round(&issue, time);
+            let mut summary = format!(
+                "Log {} to {}",
+                describe_time(rounded, started),
+                prefs.borrow().describe_ticket(&issue)
+            );
+            if !comment.is_empty() {
+                summary.push_str(&format!("\n\n{comment}"));
+            }
+
+            c.add_layer(
+                Dialog::around(TextView::new(summary))
+                    .title("Confirm")
+                    .button("Submit", move |c| {
                         c.pop_layer();
+                        upload(
+                            c,
+                            TimeLog {
+                                time_spent_seconds: time,
+                                comment: comment.clone(),
+                                ticket_number: issue.clone(),
+                                started,
+                                frontend: Frontend::Tui,
+                            },
+                            width,
+                        )
+                    })
+                    .button("Back", |c| {
                         c.pop_layer();
-                    }),
-                ),
-                Err(err) => c.add_layer(
-                    Dialog::around(TextView::new(format!("ERROR: {}", err.msg()))).button(
-                        "Okay",
-                        |c| {
-                            c.pop_layer();
-                            c.pop_layer();
-                        },
-                    ),
-                ),
-            };
+                    })
+                    .fixed_width(width),
+            );
         }
         Err(err) => c.add_layer(
-            Dialog::around(TextView::new(format!("ERROR: {}", err.msg()))).button("Okay", |c| {
+            Dialog::around(TextView::new(format!(
+                "ERROR: {}",
+                err.describe(&time_input)
+            )))
+            .button("Okay", |c| {
                 c.pop_layer();
             }),
         ),
     };
 }
+
+// Submits `log` on another thread so Jira doesn't hold up the interface
+fn upload(c: &mut Cursive, log: TimeLog, width: usize) {
+    c.add_layer(Dialog::around(TextView::new("Uploading...")));
+
+    let session = session(c);
+    let sink = c.cb_sink().clone();
+    std::thread::spawn(move || {
+        let submission = submit_or_queue(&session, &log);
+        sink.send(Box::new(move |c| {
+            if let Some(prefs) = c
+                .user_data::<Shared>()
+                .map(|shared| Rc::clone(&shared.prefs))
+            {
+                finish_upload(c, prefs, &log, submission, width);
+            }
+        }))
+        .ok();
+    });
+}
+
+fn finish_upload(
+    c: &mut Cursive,
+    prefs: PrefRef,
+    log: &TimeLog,
+    submission: Result<Submission, JiraError>,
+    width: usize,
+) {
+    match submission {
+        Ok(Submission::Submitted(worklog)) => {
+            let mut p = prefs.borrow_mut();
+            p.record_worklog(&worklog);
+            p.save().ok();
+            drop(p);
+
+            c.add_layer(
+                Dialog::around(TextView::new("Successful".to_string())).button("Okay", move |c| {
+                    c.pop_layer();
+                    c.pop_layer();
+                    c.pop_layer();
+                    refresh_week_dialog(c, width);
+                }),
+            )
+        }
+        Ok(Submission::Queued(err)) => {
+            let mut prefs = prefs.borrow_mut();
+            prefs.update_timer_state(&log.ticket_number);
+            prefs.save().ok();
+
+            c.add_layer(create_message_dialog(
+                &format!(
+                    "{err}\n\nYour worklog has been queued and will be submitted once Jira \
+                    can be reached. You can find it under Pending Worklogs."
+                ),
+                3,
+                width,
+            ))
+        }
+        Err(err) => c.add_layer(create_submit_error_dialog(err, prefs, width)),
+    };
+}
+
+// Offers a way forward for the errors the user can fix themselves
+fn create_submit_error_dialog(err: JiraError, prefs: PrefRef, width: usize) -> Box<dyn View> {
+    let dialog = Dialog::around(TextView::new(format!("ERROR: {err}"))).button("Okay", |c| {
+        c.pop_layer();
+        c.pop_layer();
+    });
+
+    let dialog = match err {
+        JiraError::Unauthorized => dialog.button("Open Setup", move |c| {
+            c.pop_layer();
+            c.pop_layer();
+            c.add_layer(create_setup_dialog(Rc::clone(&prefs), width));
+        }),
+        JiraError::IssueNotFound { .. } => dialog.button("Change Ticket", move |c| {
+            c.pop_layer();
+            c.pop_layer();
+            c.pop_layer();
+            c.add_layer(create_issue_input_dialog(Rc::clone(&prefs), width));
+        }),
+        _ => dialog,
+    };
+
+    Box::from(dialog.fixed_width(width))
+}
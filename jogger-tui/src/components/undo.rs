@@ -0,0 +1,149 @@
+use cursive::{
+    view::{Nameable, Resizable},
+    views::{Dialog, EditView, LinearLayout, TextView},
+    Cursive, View,
+};
+use jogger_core::{
+    record, seconds_to_string, Frontend, JiraSession, JournalEntry, Outcome, PrefRef, Worklog,
+    WorklogUpdate,
+};
+use std::rc::Rc;
+
+use crate::components::{create_message_dialog, session};
+
+pub fn create_undo_dialog(prefs: PrefRef, session: &JiraSession, width: usize) -> Box<dyn View> {
+    let last = {
+        let prefs = prefs.borrow();
+        prefs
+            .timer_state
+            .last_ticket
+            .clone()
+            .zip(prefs.timer_state.last_worklog_id.clone())
+    };
+
+    let Some((ticket, id)) = last else {
+        return create_message_dialog("There is nothing to undo.", 1, width);
+    };
+
+    let worklog = match session.fetch_worklog(&ticket, &id) {
+        Ok(worklog) => worklog,
+        Err(err) => return create_message_dialog(&format!("ERROR: {}", err), 1, width),
+    };
+
+    let summary = format!(
+        "{} logged to {} on {} at {:02}:{:02}\n{}",
+        seconds_to_string(worklog.seconds),
+        worklog.ticket,
+        worklog.started.date(),
+        worklog.started.hour(),
+        worklog.started.minute(),
+        worklog.comment
+    );
+
+    let p = Rc::clone(&prefs);
+    let w = worklog.clone();
+
+    Box::from(
+        Dialog::around(TextView::new(summary))
+            .title("Undo Last Log")
+            .button("Delete", move |c| delete(c, Rc::clone(&p), &w, width))
+            .button("Change Time", move |c| {
+                c.pop_layer();
+                c.add_layer(create_edit_dialog(
+                    Rc::clone(&prefs),
+                    worklog.clone(),
+                    width,
+                ));
+            })
+            .button("Cancel", |c| {
+                c.pop_layer();
+            })
+            .fixed_width(width),
+    )
+}
+
+fn create_edit_dialog(prefs: PrefRef, worklog: Worklog, width: usize) -> Box<dyn View> {
+    let view = LinearLayout::vertical()
+        .child(
+            LinearLayout::horizontal()
+                .child(TextView::new("Time: "))
+                .child(EditView::new().with_name("time").full_width()),
+        )
+        .child(
+            LinearLayout::horizontal()
+                .child(TextView::new("Comment: "))
+                .child(
+                    EditView::new()
+                        .content(&worklog.comment)
+                        .with_name("comment")
+                        .full_width(),
+                ),
+        );
+
+    Box::from(
+        Dialog::around(view)
+            .title(format!("Change Time for {}", worklog.ticket))
+            .button("Save", move |c| update(c, &prefs, &worklog, width))
+            .button("Cancel", |c| {
+                c.pop_layer();
+            })
+            .fixed_width(width),
+    )
+}
+
+fn delete(c: &mut Cursive, prefs: PrefRef, worklog: &Worklog, width: usize) {
+    let result = session(c).delete_worklog(&worklog.ticket, &worklog.id);
+
+    match result {
+        Ok(_) => {
+            record(&JournalEntry::edited(
+                Frontend::Tui,
+                worklog,
+                Outcome::Deleted,
+            ))
+            .ok();
+
+            let mut prefs = prefs.borrow_mut();
+            prefs.timer_state.last_worklog_id = None;
+            prefs.save().ok();
+            c.add_layer(create_message_dialog("Worklog deleted", 2, width));
+        }
+        Err(err) => c.add_layer(create_message_dialog(&format!("ERROR: {}", err), 1, width)),
+    }
+}
+
+fn update(c: &mut Cursive, prefs: &PrefRef, worklog: &Worklog, width: usize) {
+    let time = c.find_name::<EditView>("time").unwrap().get_content();
+    let comment = c.find_name::<EditView>("comment").unwrap().get_content();
+
+    let parsed = prefs.borrow().parse_duration(&time);
+    let seconds = match parsed {
+        Ok(seconds) => seconds,
+        Err(err) => {
+            c.add_layer(create_message_dialog(
+                &format!("ERROR: {}", err.describe(&time)),
+                1,
+                width,
+            ));
+            return;
+        }
+    };
+
+    let update = WorklogUpdate {
+        time_spent_seconds: Some(seconds),
+        comment: Some(comment.to_string()),
+    };
+
+    match session(c).update_worklog(&worklog.ticket, &worklog.id, &update) {
+        Ok(updated) => {
+            record(&JournalEntry::edited(
+                Frontend::Tui,
+                &updated,
+                Outcome::Updated,
+            ))
+            .ok();
+            c.add_layer(create_message_dialog("Worklog updated", 2, width))
+        }
+        Err(err) => c.add_layer(create_message_dialog(&format!("ERROR: {}", err), 1, width)),
+    }
+}
@@ -0,0 +1,184 @@
+use cursive::{
+    align::HAlign,
+    view::{Nameable, Resizable, Scrollable},
+    views::{Button, Dialog, LinearLayout, TextView},
+    Cursive, View,
+};
+use jogger_core::{
+    local_now, query_journal, recent_tickets, week_of, JiraSession, JournalQuery, PrefRef,
+    Timesheet,
+};
+use std::rc::Rc;
+use time::Date;
+
+use crate::components::{create_log_on_day_dialog, is_on_top, session, Shared};
+
+const TICKET_WIDTH: usize = 14;
+const CELL_WIDTH: usize = 8;
+const WEEK_DIALOG: &str = "my_week";
+
+/// Shows My Week, loading it in the background so Jira doesn't hold up the interface.
+pub fn show_week_dialog(c: &mut Cursive, width: usize) {
+    c.add_layer(
+        Dialog::around(TextView::new("Loading your week..."))
+            .title("My Week")
+            .button("Close", |c| {
+                c.pop_layer();
+            })
+            .fixed_width(width)
+            .with_name(WEEK_DIALOG),
+    );
+
+    let session = session(c);
+    let sink = c.cb_sink().clone();
+    std::thread::spawn(move || {
+        let week = load_week(&session);
+        sink.send(Box::new(move |c| {
+            // Unless it was closed while loading
+            if !is_on_top(c, WEEK_DIALOG) {
+                return;
+            }
+            let Some(prefs) = c
+                .user_data::<Shared>()
+                .map(|shared| Rc::clone(&shared.prefs))
+            else {
+                return;
+            };
+
+            c.pop_layer();
+            c.add_layer(create_week_dialog(prefs, week, width));
+        }))
+        .ok();
+    });
+}
+
+/// Loads My Week again if it's the dialog on top, so time just logged from one of its cells
+/// shows up.
+pub fn refresh_week_dialog(c: &mut Cursive, width: usize) {
+    if is_on_top(c, WEEK_DIALOG) {
+        c.pop_layer();
+        show_week_dialog(c, width);
+    }
+}
+
+// The week as it was loaded, along with a note about where it came from
+struct Week {
+    monday: Date,
+    sunday: Date,
+    timesheet: Timesheet,
+    note: String,
+}
+
+fn load_week(session: &JiraSession) -> Week {
+    let today = local_now().date();
+    let (monday, sunday) = week_of(today);
+
+    // Jira is the source of truth, but the journal still answers when it can't be reached
+    let (mut timesheet, note) = match session.fetch_worklogs(monday, sunday) {
+        Ok(worklogs) => (Timesheet::from_worklogs(today, &worklogs), String::new()),
+        Err(err) => {
+            let entries = query_journal(&JournalQuery {
+                from: Some(monday),
+                to: Some(sunday),
+                ..Default::default()
+            })
+            .unwrap_or_default();
+
+            (
+                Timesheet::from_journal(today, &entries),
+                format!("{err}\nShowing worklogs from the local journal instead.\n"),
+            )
+        }
+    };
+
+    // Recent tickets get a row even when they have nothing logged yet, so time can be added
+    for ticket in recent_tickets(5).unwrap_or_default() {
+        timesheet.rows.entry(ticket).or_default();
+    }
+
+    Week {
+        monday,
+        sunday,
+        timesheet,
+        note,
+    }
+}
+
+fn create_week_dialog(prefs: PrefRef, week: Week, width: usize) -> Box<dyn View> {
+    let Week {
+        monday,
+        sunday,
+        timesheet,
+        note,
+    } = week;
+
+    let mut grid = LinearLayout::vertical();
+
+    let mut header = LinearLayout::horizontal().child(text("Ticket", TICKET_WIDTH, HAlign::Left));
+    for day in timesheet.days() {
+        header.add_child(text(
+            &format!("{} {:02}", &day.weekday().to_string()[..3], day.day()),
+            CELL_WIDTH,
+            HAlign::Right,
+        ));
+    }
+    grid.add_child(header.child(text("Total", CELL_WIDTH, HAlign::Right)));
+
+    for (ticket, row) in &timesheet.rows {
+        let mut line = LinearLayout::horizontal().child(text(ticket, TICKET_WIDTH, HAlign::Left));
+
+        for (day, seconds) in timesheet.days().into_iter().zip(row) {
+            if *seconds > 0 {
+                line.add_child(text(&hours(*seconds), CELL_WIDTH, HAlign::Right));
+            } else {
+                // Empty cells jump straight into logging time for that ticket and day
+                let (p, ticket) = (Rc::clone(&prefs), ticket.clone());
+                line.add_child(
+                    LinearLayout::horizontal()
+                        .child(TextView::new("").full_width())
+                        .child(Button::new_raw("  + ", move |c| {
+                            c.add_layer(create_log_on_day_dialog(
+                                Rc::clone(&p),
+                                ticket.clone(),
+                                day,
+                                width,
+                            ))
+                        }))
+                        .fixed_width(CELL_WIDTH),
+                );
+            }
+        }
+
+        let total: usize = row.iter().sum();
+        grid.add_child(line.child(text(&hours(total), CELL_WIDTH, HAlign::Right)));
+    }
+
+    let mut totals = LinearLayout::horizontal().child(text("Total", TICKET_WIDTH, HAlign::Left));
+    for seconds in timesheet.day_totals() {
+        totals.add_child(text(&hours(seconds), CELL_WIDTH, HAlign::Right));
+    }
+    grid.add_child(totals.child(text(&hours(timesheet.total()), CELL_WIDTH, HAlign::Right)));
+
+    let view = LinearLayout::vertical()
+        .child(TextView::new(note))
+        .child(grid.scrollable());
+
+    Box::from(
+        Dialog::around(view)
+            .title(format!("My Week ({monday} to {sunday})"))
+            .button("Refresh", move |c| refresh_week_dialog(c, width))
+            .button("Close", |c| {
+                c.pop_layer();
+            })
+            .fixed_width(width)
+            .with_name(WEEK_DIALOG),
+    )
+}
+
+fn text(content: &str, width: usize, align: HAlign) -> impl View {
+    TextView::new(content).h_align(align).fixed_width(width)
+}
+
+fn hours(seconds: usize) -> String {
+    format!("{:.2}", seconds as f64 / 3600.0)
+}
@@ -1,17 +1,32 @@
+mod cli;
 mod components;
 
-use components::create_menu_dialog;
+use components::{create_menu_dialog, create_message_dialog, Shared};
 use cursive::{
     theme::{BaseColor::Green, Color::Dark},
+    view::Resizable,
+    views::{Dialog, TextView},
     Cursive, CursiveExt,
 };
-use jogger_core::Preferences;
+use jogger_core::{replay_queue, watch_config, Preferences};
 use std::{cell::RefCell, rc::Rc};
 
 const WIDTH: usize = 86;
 
 fn main() {
-    let prefs = Rc::new(RefCell::new(Preferences::load().unwrap_or_default()));
+    let mut args: Vec<String> = std::env::args().skip(1).collect();
+    let options = match cli::take_options(&mut args) {
+        Ok(options) => options,
+        Err(err) => {
+            eprintln!("error: {err}");
+            std::process::exit(2);
+        }
+    };
+    if !args.is_empty() {
+        std::process::exit(cli::run(&args, &options));
+    }
+
+    let prefs = Preferences::load_with(&options);
 
     let mut c = Cursive::new();
     c.add_global_callback('q', |c| {
@@ -24,7 +39,72 @@ fn main() {
 
     c.update_theme(|theme| theme.palette.set_color("Background", Dark(Green)));
     c.set_window_title("Jogger");
-    c.add_layer(create_menu_dialog(prefs, WIDTH));
+
+    // Carrying on with default settings would overwrite the user's file the next time they're saved
+    let prefs = match prefs {
+        Ok(prefs) => Rc::new(RefCell::new(prefs)),
+        Err(err) => {
+            c.add_layer(
+                Dialog::around(TextView::new(format!(
+                    "Your settings could not be loaded:\n\n{err}\n\nFix the file and start jogger again."
+                )))
+                .title("Settings Error")
+                .button("Quit", |c| c.quit())
+                .fixed_width(WIDTH),
+            );
+            c.run();
+            return;
+        }
+    };
+
+    // Edits made elsewhere replace the shared settings in place, so every open view sees them
+    c.set_user_data(Shared::new(Rc::clone(&prefs)));
+    c.add_layer(create_menu_dialog(Rc::clone(&prefs), WIDTH));
+    if let Some(message) = prefs.borrow().describe_key_errors() {
+        c.add_layer(create_message_dialog(&message, 1, WIDTH));
+    }
+
+    let sink = c.cb_sink().clone();
+    let _watcher = watch_config(options.clone(), move |reloaded| {
+        sink.send(Box::new(move |c| match reloaded {
+            Ok(reloaded) => {
+                let problems = reloaded.describe_key_errors();
+                c.with_user_data(|shared: &mut Shared| {
+                    *shared.prefs.borrow_mut() = reloaded;
+                    shared.renew_session();
+                });
+                if let Some(message) = problems {
+                    c.add_layer(create_message_dialog(&message, 1, WIDTH));
+                }
+            }
+            Err(err) => {
+                let message = format!(
+                    "Your settings could not be reloaded:\n\n{err}\n\nThe previous settings are still in use."
+                );
+                c.add_layer(create_message_dialog(&message, 1, WIDTH));
+            }
+        }))
+        .ok();
+    });
+
+    // Retry anything that failed to submit last time without holding up the interface
+    let sink = c.cb_sink().clone();
+    let session = components::session(&mut c);
+    std::thread::spawn(move || {
+        if let Ok(report) = replay_queue(&session) {
+            if !report.submitted.is_empty() {
+                let message = format!(
+                    "Submitted {} pending worklog(s), {} still pending",
+                    report.submitted.len(),
+                    report.remaining
+                );
+                sink.send(Box::new(move |c| {
+                    c.add_layer(create_message_dialog(&message, 1, WIDTH))
+                }))
+                .ok();
+            }
+        }
+    });
 
     c.run();
 }